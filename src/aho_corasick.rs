@@ -0,0 +1,183 @@
+//! Searches for many fixed strings at once.
+//!
+//! An Aho-Corasick automaton is a trie of all the patterns plus a failure
+//! link from every node to the longest suffix that is also in the trie. The
+//! haystack is read once, one char at a time, however many patterns there
+//! are, so searching for hundreds of identifiers costs about the same as
+//! searching for one.
+
+use std::{collections::HashMap, collections::VecDeque, ops::Range};
+
+use crate::utf8;
+
+#[derive(Debug)]
+pub struct AhoCorasick {
+    states: Vec<State>,
+    ignore_case: bool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    next: HashMap<char, usize>,
+    fail: usize,
+    /// How many chars deep in the trie this state is.
+    depth: usize,
+    /// The length in chars of the longest pattern ending at this state,
+    /// either here or further down the failure links.
+    output: Option<usize>,
+}
+
+impl AhoCorasick {
+    pub fn new<P: AsRef<str>>(patterns: &[P], ignore_case: bool) -> AhoCorasick {
+        let mut states = vec![State::default()];
+
+        for pattern in patterns {
+            let mut current = 0;
+            for c in pattern.as_ref().chars() {
+                let c = if ignore_case { utf8::fold(c) } else { c };
+                current = match states[current].next.get(&c) {
+                    Some(&next) => next,
+                    None => {
+                        let depth = states[current].depth + 1;
+                        states.push(State {
+                            depth,
+                            ..State::default()
+                        });
+                        let next = states.len() - 1;
+                        states[current].next.insert(c, next);
+                        next
+                    }
+                };
+            }
+            states[current].output = Some(states[current].depth);
+        }
+
+        // Breadth first, so that a state's failure link is always finished
+        // before the states below it need it.
+        let mut queue: VecDeque<usize> = states[0].next.values().copied().collect();
+        while let Some(state) = queue.pop_front() {
+            let children: Vec<(char, usize)> =
+                states[state].next.iter().map(|(&c, &s)| (c, s)).collect();
+            for (c, child) in children {
+                let mut fail = states[state].fail;
+                let target = loop {
+                    if let Some(&next) = states[fail].next.get(&c) {
+                        break next;
+                    }
+                    if fail == 0 {
+                        break 0;
+                    }
+                    fail = states[fail].fail;
+                };
+                states[child].fail = target;
+                if states[child].output.is_none() {
+                    states[child].output = states[target].output;
+                }
+                queue.push_back(child);
+            }
+        }
+
+        AhoCorasick {
+            states,
+            ignore_case,
+        }
+    }
+
+    fn step(&self, mut state: usize, c: char) -> usize {
+        loop {
+            if let Some(&next) = self.states[state].next.get(&c) {
+                return next;
+            }
+            if state == 0 {
+                return 0;
+            }
+            state = self.states[state].fail;
+        }
+    }
+
+    /// Finds the leftmost match at or after `start`, preferring the longest
+    /// pattern when several start at the same place.
+    pub fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        if start > haystack.len() {
+            return None;
+        }
+        if self.states[0].output.is_some() {
+            // The empty pattern matches right away.
+            return Some(start..start);
+        }
+
+        // `offsets[i]` is the byte offset after reading `i` chars, so a
+        // match found in chars can be turned back into bytes.
+        let mut offsets = vec![start];
+        let mut best: Option<(usize, usize)> = None;
+        let mut state = 0;
+        let mut at = start;
+        loop {
+            let (c, width) = utf8::decode(haystack, at);
+            if width == 0 {
+                break;
+            }
+            at += width;
+            offsets.push(at);
+            let read = offsets.len() - 1;
+
+            state = match c {
+                Some(c) if self.ignore_case => self.step(state, utf8::fold(c)),
+                Some(c) => self.step(state, c),
+                // Invalid UTF-8 never matches, so start over after it.
+                None => 0,
+            };
+
+            if let Some(len) = self.states[state].output {
+                let match_start = read - len;
+                if best.is_none_or(|(best_start, _)| match_start <= best_start) {
+                    best = Some((match_start, at));
+                }
+            }
+            // Once the text we're in the middle of starts after the best
+            // match, nothing further along can beat it.
+            if best.is_some_and(|(best_start, _)| read - self.states[state].depth > best_start) {
+                break;
+            }
+        }
+
+        best.map(|(match_start, end)| offsets[match_start]..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'h>(patterns: &[&str], haystack: &'h str) -> Option<&'h str> {
+        let ac = AhoCorasick::new(patterns, false);
+        ac.find_at(haystack.as_bytes(), 0).map(|m| &haystack[m])
+    }
+
+    #[test]
+    fn leftmost_longest() {
+        assert_eq!(Some("she"), find(&["he", "she", "hers"], "ushers"));
+        assert_eq!(Some("abcd"), find(&["bc", "abcd", "abc"], "xabcde"));
+        assert_eq!(Some("bcd"), find(&["bcd", "cdefgh"], "abcdefgh"));
+        assert_eq!(None, find(&["sun", "moon"], "stars"));
+        assert_eq!(None, find(&[], "stars"));
+    }
+
+    #[test]
+    fn ignore_case_keeps_byte_offsets() {
+        let ac = AhoCorasick::new(&["kelvin", "iDEATH"], true);
+        let haystack = "\u{212A}ELVIN and ideath".as_bytes();
+
+        assert_eq!(Some(0..8), ac.find_at(haystack, 0));
+        assert_eq!(Some(13..19), ac.find_at(haystack, 1));
+    }
+
+    #[test]
+    fn many_patterns() {
+        let patterns: Vec<String> = (0..500).map(|i| format!("id{i:04}")).collect();
+        let ac = AhoCorasick::new(&patterns, false);
+
+        assert_eq!(Some(8..14), ac.find_at(b"lookup: id0420;", 0));
+        assert_eq!(None, ac.find_at(b"lookup: id9999;", 0));
+    }
+}
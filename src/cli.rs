@@ -0,0 +1,653 @@
+//! Splits command-line arguments into options and positional arguments.
+//!
+//! Every option is described once in `OPTIONS`; both the parser and the
+//! `--help` text are driven by that table. The usual conventions apply:
+//! short flags can be bundled (`-iv`), a short option's value may follow it
+//! directly (`-A3`) or come as the next argument, long options take their
+//! value as `--name=value` or `--name value`, and `--` ends the options.
+//!
+//! Every option can also be given a default in the environment, in a
+//! variable named after it: `--ignore-case` is `MINIGREP_IGNORE_CASE` and
+//! `--context` is `MINIGREP_CONTEXT`. Flags take a boolean value there.
+//! The patterns are the exception, since one from the environment would
+//! turn the QUERY argument into a path.
+
+use std::fmt;
+
+/// A single command-line option.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Opt {
+    pub short: Option<char>,
+    pub long: &'static str,
+    /// The name of the option's value in the help text. Options without
+    /// one are plain on/off flags.
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+pub const OPTIONS: &[Opt] = &[
+    Opt {
+        short: Some('e'),
+        long: "regexp",
+        value: Some("PATTERN"),
+        help: "Search for PATTERN; may be given more than once",
+    },
+    Opt {
+        short: Some('f'),
+        long: "file",
+        value: Some("FILE"),
+        help: "Search for the patterns in FILE, one per line",
+    },
+    Opt {
+        short: Some('i'),
+        long: "ignore-case",
+        value: None,
+        help: "Search case-insensitively",
+    },
+    Opt {
+        short: Some('s'),
+        long: "case-sensitive",
+        value: None,
+        help: "Search case-sensitively",
+    },
+    Opt {
+        short: Some('S'),
+        long: "smart-case",
+        value: None,
+        help: "Ignore case unless a pattern has an uppercase letter in it",
+    },
+    Opt {
+        short: Some('E'),
+        long: "regex",
+        value: None,
+        help: "Treat QUERY as a regular expression",
+    },
+    Opt {
+        short: Some('F'),
+        long: "fixed-strings",
+        value: None,
+        help: "Treat QUERY as a plain string (the default)",
+    },
+    Opt {
+        short: Some('v'),
+        long: "invert-match",
+        value: None,
+        help: "Print the lines that do not match",
+    },
+    Opt {
+        short: Some('w'),
+        long: "word-regexp",
+        value: None,
+        help: "Only match whole words",
+    },
+    Opt {
+        short: Some('x'),
+        long: "line-regexp",
+        value: None,
+        help: "Only match whole lines",
+    },
+    Opt {
+        short: Some('A'),
+        long: "after-context",
+        value: Some("NUM"),
+        help: "Print NUM lines after each match",
+    },
+    Opt {
+        short: Some('B'),
+        long: "before-context",
+        value: Some("NUM"),
+        help: "Print NUM lines before each match",
+    },
+    Opt {
+        short: Some('C'),
+        long: "context",
+        value: Some("NUM"),
+        help: "Print NUM lines before and after each match",
+    },
+    Opt {
+        short: Some('o'),
+        long: "only-matching",
+        value: None,
+        help: "Print only the matched parts of each line, one per line",
+    },
+    Opt {
+        short: Some('n'),
+        long: "line-number",
+        value: None,
+        help: "Print the line number of each line",
+    },
+    Opt {
+        short: None,
+        long: "column",
+        value: None,
+        help: "Print the column of the first match in each line",
+    },
+    Opt {
+        short: None,
+        long: "column-unit",
+        value: Some("UNIT"),
+        help: "Count columns in bytes (the default) or chars",
+    },
+    Opt {
+        short: Some('b'),
+        long: "byte-offset",
+        value: None,
+        help: "Print the byte offset of each line in its file",
+    },
+    Opt {
+        short: Some('r'),
+        long: "replace",
+        value: Some("TEXT"),
+        help: "Print matches replaced by TEXT, where -E allows $1 and ${name}",
+    },
+    Opt {
+        short: None,
+        long: "in-place",
+        value: None,
+        help: "Rewrite files with the --replace replacements made",
+    },
+    Opt {
+        short: None,
+        long: "backup",
+        value: None,
+        help: "Keep the original of each rewritten file as FILE.bak",
+    },
+    Opt {
+        short: None,
+        long: "dry-run",
+        value: None,
+        help: "Print the changes --in-place would make as a diff",
+    },
+    Opt {
+        short: None,
+        long: "interactive",
+        value: None,
+        help: "Ask before making each --in-place replacement",
+    },
+    Opt {
+        short: Some('c'),
+        long: "count",
+        value: None,
+        help: "Print only the number of matching lines in each file",
+    },
+    Opt {
+        short: Some('l'),
+        long: "files-with-matches",
+        value: None,
+        help: "Print only the names of files with matches",
+    },
+    Opt {
+        short: Some('L'),
+        long: "files-without-match",
+        value: None,
+        help: "Print only the names of files without matches",
+    },
+    Opt {
+        short: Some('q'),
+        long: "quiet",
+        value: None,
+        help: "Print nothing; exit with 0 as soon as a line matches",
+    },
+    Opt {
+        short: None,
+        long: "json",
+        value: None,
+        help: "Describe the results as JSON Lines",
+    },
+    Opt {
+        short: None,
+        long: "color",
+        value: Some("WHEN"),
+        help: "When to use colors: auto, always or never",
+    },
+    Opt {
+        short: None,
+        long: "line-buffered",
+        value: None,
+        help: "Write each line out as soon as it is found",
+    },
+    Opt {
+        short: None,
+        long: "max-depth",
+        value: Some("NUM"),
+        help: "Descend at most NUM directories deep",
+    },
+    Opt {
+        short: None,
+        long: "hidden",
+        value: None,
+        help: "Search hidden files and directories",
+    },
+    Opt {
+        short: None,
+        long: "follow",
+        value: None,
+        help: "Follow symbolic links",
+    },
+    Opt {
+        short: None,
+        long: "no-ignore",
+        value: None,
+        help: "Don't skip files listed in .gitignore and .ignore",
+    },
+    Opt {
+        short: Some('H'),
+        long: "with-filename",
+        value: None,
+        help: "Print the file name for each match",
+    },
+    Opt {
+        short: None,
+        long: "no-filename",
+        value: None,
+        help: "Never print file names",
+    },
+    Opt {
+        short: None,
+        long: "verbose",
+        value: None,
+        help: "Describe what is being searched on stderr",
+    },
+    Opt {
+        short: Some('h'),
+        long: "help",
+        value: None,
+        help: "Print this help and exit",
+    },
+    Opt {
+        short: Some('V'),
+        long: "version",
+        value: None,
+        help: "Print the version and exit",
+    },
+];
+
+/// One parsed argument.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Arg {
+    /// An option, with its value if it takes one.
+    Opt(&'static Opt, Option<String>),
+    Positional(String),
+}
+
+/// Why arguments could not be turned into a `Config`. `Help` and `Version`
+/// are not really errors, but like errors they mean there is no search to
+/// run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Help,
+    Version,
+    Usage(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Help => f.write_str(&help()),
+            Error::Version => f.write_str(&version()),
+            Error::Usage(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<&str> for Error {
+    fn from(msg: &str) -> Error {
+        Error::Usage(msg.to_string())
+    }
+}
+
+/// Parses `args`, which should not include the program name.
+pub fn parse(args: &[String]) -> Result<Vec<Arg>, Error> {
+    let mut parsed = Vec::new();
+    let mut args = args.iter();
+    let mut options_done = false;
+
+    while let Some(arg) = args.next() {
+        if options_done || arg == "-" || !arg.starts_with('-') {
+            parsed.push(Arg::Positional(arg.clone()));
+        } else if arg == "--" {
+            options_done = true;
+        } else if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let opt = OPTIONS
+                .iter()
+                .find(|opt| opt.long == name)
+                .ok_or_else(|| Error::Usage(format!("unknown option '--{name}'")))?;
+
+            let value = match (opt.value, inline) {
+                (Some(_), Some(value)) => Some(value),
+                (Some(_), None) => Some(next_value(&mut args, &format!("--{name}"))?),
+                (None, Some(_)) => {
+                    return Err(Error::Usage(format!(
+                        "option '--{name}' doesn't take a value"
+                    )));
+                }
+                (None, None) => None,
+            };
+            parsed.push(Arg::Opt(opt, value));
+        } else {
+            let flags = &arg[1..];
+            for (i, c) in flags.char_indices() {
+                let opt = OPTIONS
+                    .iter()
+                    .find(|opt| opt.short == Some(c))
+                    .ok_or_else(|| Error::Usage(format!("unknown option '-{c}'")))?;
+
+                if opt.value.is_none() {
+                    parsed.push(Arg::Opt(opt, None));
+                    continue;
+                }
+
+                // The rest of the bundle, if any, is the value.
+                let rest = &flags[i + c.len_utf8()..];
+                let value = if rest.is_empty() {
+                    next_value(&mut args, &format!("-{c}"))?
+                } else {
+                    rest.to_string()
+                };
+                parsed.push(Arg::Opt(opt, Some(value)));
+                break;
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn next_value(args: &mut std::slice::Iter<'_, String>, name: &str) -> Result<String, Error> {
+    args.next()
+        .cloned()
+        .ok_or_else(|| Error::Usage(format!("option '{name}' requires a value")))
+}
+
+/// Options that decide the same thing between them. One of them on the
+/// command line overrides all of them in the environment.
+const ENV_GROUPS: &[&[&str]] = &[
+    &["ignore-case", "case-sensitive", "smart-case"],
+    &["after-context", "before-context", "context"],
+];
+
+/// The environment variable that sets a default for `opt`.
+pub fn env_name(opt: &Opt) -> String {
+    format!("MINIGREP_{}", opt.long.to_uppercase().replace('-', "_"))
+}
+
+/// Reads the options set in the environment, using `var` to look up each
+/// variable. Options in `given`, which came from the command line, are
+/// skipped, so that they always win. Flags are only included if they are
+/// set to true. `--regexp`, `--file`, `--help` and `--version` can't be set
+/// this way.
+pub fn env_args(given: &[Arg], var: impl Fn(&str) -> Option<String>) -> Result<Vec<Arg>, Error> {
+    let mut args = Vec::new();
+
+    for opt in OPTIONS {
+        let group = ENV_GROUPS
+            .iter()
+            .copied()
+            .find(|group| group.contains(&opt.long))
+            .unwrap_or(std::slice::from_ref(&opt.long));
+        let on_command_line = given
+            .iter()
+            .any(|arg| matches!(arg, Arg::Opt(given, _) if group.contains(&given.long)));
+        if on_command_line || matches!(opt.long, "regexp" | "file" | "help" | "version") {
+            continue;
+        }
+
+        // IGNORE_CASE is how case-insensitive search was first turned on,
+        // so it still works, but MINIGREP_IGNORE_CASE wins over it.
+        let legacy = (opt.long == "ignore-case").then(|| String::from("IGNORE_CASE"));
+        let Some((name, value)) = [Some(env_name(opt)), legacy]
+            .into_iter()
+            .flatten()
+            .find_map(|name| var(&name).map(|value| (name, value)))
+        else {
+            continue;
+        };
+
+        match opt.value {
+            Some("NUM") => {
+                // Check numbers here, so that the error names the variable.
+                value.parse::<usize>().map_err(|_| {
+                    Error::Usage(format!(
+                        "invalid value '{value}' for {name}: expected a non-negative number"
+                    ))
+                })?;
+                args.push(Arg::Opt(opt, Some(value)));
+            }
+            Some(_) => args.push(Arg::Opt(opt, Some(value))),
+            None => {
+                if boolean(&name, &value)? {
+                    args.push(Arg::Opt(opt, None));
+                }
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+/// Parses the value of a boolean environment variable. An empty value is
+/// the same as not setting it.
+fn boolean(name: &str, value: &str) -> Result<bool, Error> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "" | "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(Error::Usage(format!(
+            "invalid value '{value}' for {name}: expected true or false"
+        ))),
+    }
+}
+
+/// Parses the value of a numeric option.
+pub fn number(opt: &Opt, value: Option<String>) -> Result<usize, Error> {
+    let value = value.unwrap_or_default();
+    value.parse().map_err(|_| {
+        Error::Usage(format!(
+            "invalid value '{value}' for '--{}': expected a non-negative number",
+            opt.long
+        ))
+    })
+}
+
+pub fn version() -> String {
+    format!("minigrep {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Builds the `--help` text from `OPTIONS`.
+pub fn help() -> String {
+    let mut help = String::from(
+        "\
+Usage: minigrep [OPTIONS] QUERY [PATH]...
+       minigrep [OPTIONS] -e PATTERN... [PATH]...
+
+Prints the lines of each PATH that contain QUERY. A PATH may be a file or
+a directory, which is searched recursively. Without a PATH, or when it is
+'-', standard input is searched. File names are printed when there is more
+than one file to search.
+
+When patterns are given with -e or -f, there is no QUERY argument, and a
+line is printed if any of the patterns matches it.
+
+Every option but -e, -f, -h and -V can also be set in the environment, in a
+variable named after it, such as MINIGREP_IGNORE_CASE=true or
+MINIGREP_CONTEXT=2. Options given on the command line win.
+
+Exits with 0 if a line matched, 1 if none did, and 2 if there was an error.
+
+Options:
+",
+    );
+
+    let flags: Vec<String> = OPTIONS
+        .iter()
+        .map(|opt| {
+            let short = match opt.short {
+                Some(c) => format!("-{c}, "),
+                None => String::from("    "),
+            };
+            let value = match opt.value {
+                Some(value) => format!("={value}"),
+                None => String::new(),
+            };
+            format!("{short}--{}{value}", opt.long)
+        })
+        .collect();
+    let width = flags.iter().map(String::len).max().unwrap_or(0);
+
+    for (flags, opt) in flags.iter().zip(OPTIONS) {
+        help.push_str(&format!("  {flags:width$}  {}\n", opt.help));
+    }
+
+    help
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn names(parsed: &[Arg]) -> Vec<String> {
+        parsed
+            .iter()
+            .map(|arg| match arg {
+                Arg::Opt(opt, Some(value)) => format!("{}={value}", opt.long),
+                Arg::Opt(opt, None) => opt.long.to_string(),
+                Arg::Positional(arg) => arg.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bundles_and_values() {
+        let parsed = parse(&args(&[
+            "-iv",
+            "-A3",
+            "-B",
+            "2",
+            "--color=never",
+            "sun",
+            "-",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                "ignore-case",
+                "invert-match",
+                "after-context=3",
+                "before-context=2",
+                "color=never",
+                "sun",
+                "-"
+            ],
+            names(&parsed)
+        );
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let parsed = parse(&args(&["--ignore-case", "--", "-v", "--x"])).unwrap();
+
+        assert_eq!(vec!["ignore-case", "-v", "--x"], names(&parsed));
+
+        let parsed = parse(&args(&["-lL", "--follow"])).unwrap();
+        assert_eq!(
+            vec!["files-with-matches", "files-without-match", "follow"],
+            names(&parsed)
+        );
+    }
+
+    #[test]
+    fn reports_bad_options() {
+        assert_eq!(
+            Err(Error::Usage(String::from("unknown option '--colour'"))),
+            parse(&args(&["--colour", "sun"]))
+        );
+        assert_eq!(
+            Err(Error::Usage(String::from("unknown option '-Q'"))),
+            parse(&args(&["-iQ"]))
+        );
+        assert!(parse(&args(&["--invert-match=yes"])).is_err());
+        assert!(parse(&args(&["sun", "-A"])).is_err());
+    }
+
+    #[test]
+    fn reads_options_from_the_environment() {
+        let env = |pairs: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                pairs
+                    .iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        let given = parse(&args(&["--color=always", "sun"])).unwrap();
+
+        let parsed = env_args(
+            &given,
+            env(&[
+                ("MINIGREP_IGNORE_CASE", "yes"),
+                ("IGNORE_CASE", "0"),
+                ("MINIGREP_INVERT_MATCH", "0"),
+                ("MINIGREP_REGEXP", "moon"),
+                ("MINIGREP_AFTER_CONTEXT", "3"),
+                // Only IGNORE_CASE goes without the MINIGREP_ prefix.
+                ("INVERT_MATCH", "1"),
+                ("CONTEXT", "abc"),
+                ("COLOR", "red"),
+                ("MINIGREP_CONTEXT", "2"),
+                ("MINIGREP_COLOR", "never"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["ignore-case", "after-context=3", "context=2"],
+            names(&parsed)
+        );
+
+        assert_eq!(
+            Err(Error::Usage(String::from(
+                "invalid value 'maybe' for IGNORE_CASE: expected true or false"
+            ))),
+            env_args(&[], env(&[("IGNORE_CASE", "maybe")]))
+        );
+        assert!(env_args(&[], env(&[("MINIGREP_MAX_DEPTH", "-1")])).is_err());
+
+        // -S on the command line beats -i from the environment, even though
+        // -S on its own doesn't override -i.
+        let given = parse(&args(&["-S", "Sun"])).unwrap();
+        assert!(env_args(&given, env(&[("IGNORE_CASE", "1")]))
+            .unwrap()
+            .is_empty());
+
+        // Likewise -C beats -A and -B, which would otherwise win over it.
+        let given = parse(&args(&["-C1", "sun"])).unwrap();
+        assert!(env_args(
+            &given,
+            env(&[
+                ("MINIGREP_AFTER_CONTEXT", "3"),
+                ("MINIGREP_BEFORE_CONTEXT", "3")
+            ])
+        )
+        .unwrap()
+        .is_empty());
+    }
+
+    #[test]
+    fn help_lists_every_option() {
+        let help = help();
+
+        for opt in OPTIONS {
+            assert!(help.contains(&format!("--{}", opt.long)));
+        }
+        assert!(help.contains("  -A, --after-context=NUM  "));
+    }
+}
@@ -0,0 +1,157 @@
+//! ANSI colors for terminal output.
+//!
+//! Whether to color is decided by a `ColorChoice`; what the colors are is
+//! decided by a `Palette`, which can be customized with the
+//! `MINIGREP_COLORS` environment variable. It uses the same format as
+//! GNU grep's `GREP_COLORS`, for example `ms=01;32:fn=34:se=`.
+
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    str::FromStr,
+};
+
+/// When to use colors, as given to `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color only when writing to a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Decides whether output to stdout should be colored.
+    pub fn should_color(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                // See https://no-color.org: any non-empty value disables color.
+                let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                let dumb = env::var_os("TERM").is_some_and(|term| term == "dumb");
+                !no_color && !dumb && io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<ColorChoice, &'static str> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err("color must be one of auto, always or never"),
+        }
+    }
+}
+
+/// The SGR sequences used for each part of the output. An empty sequence
+/// leaves that part uncolored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    /// Matched text in a matching line (`ms`).
+    pub matched: String,
+    /// Matched text in a context line (`mc`).
+    pub context_matched: String,
+    /// File names (`fn`).
+    pub path: String,
+    /// Line numbers and columns (`ln`).
+    pub line_number: String,
+    /// Byte offsets (`bn`).
+    pub byte_offset: String,
+    /// The `:`, `-` and `--` separators (`se`).
+    pub separator: String,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            matched: String::from("01;31"),
+            context_matched: String::from("01;31"),
+            path: String::from("35"),
+            line_number: String::from("32"),
+            byte_offset: String::from("32"),
+            separator: String::from("36"),
+        }
+    }
+}
+
+impl Palette {
+    /// Reads `MINIGREP_COLORS`, falling back to the defaults.
+    pub fn from_env() -> Palette {
+        match env::var("MINIGREP_COLORS") {
+            Ok(spec) => Palette::parse(&spec),
+            Err(_) => Palette::default(),
+        }
+    }
+
+    /// Applies a `GREP_COLORS` style spec on top of the defaults. Unknown
+    /// capabilities and malformed values are ignored, as grep does.
+    pub fn parse(spec: &str) -> Palette {
+        let mut palette = Palette::default();
+
+        for entry in spec.split(':') {
+            let (key, value) = match entry.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            if !value.chars().all(|c| c.is_ascii_digit() || c == ';') {
+                continue;
+            }
+
+            let value = value.to_string();
+            match key {
+                "mt" => {
+                    palette.matched = value.clone();
+                    palette.context_matched = value;
+                }
+                "ms" => palette.matched = value,
+                "mc" => palette.context_matched = value,
+                "fn" => palette.path = value,
+                "ln" => palette.line_number = value,
+                "bn" => palette.byte_offset = value,
+                "se" => palette.separator = value,
+                _ => {}
+            }
+        }
+
+        palette
+    }
+}
+
+/// Writes `bytes` wrapped in the SGR sequence `sgr`, if there is one.
+pub(crate) fn paint<W: Write + ?Sized>(wtr: &mut W, sgr: &str, bytes: &[u8]) -> io::Result<()> {
+    if sgr.is_empty() {
+        return wtr.write_all(bytes);
+    }
+    write!(wtr, "\x1b[{sgr}m")?;
+    wtr.write_all(bytes)?;
+    wtr.write_all(b"\x1b[0m")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_grep_colors_specs() {
+        let palette = Palette::parse("ms=01;32:fn=:se=bogus:xx=1:mt");
+
+        assert_eq!("01;32", palette.matched);
+        assert_eq!("01;31", palette.context_matched);
+        assert_eq!("", palette.path);
+        assert_eq!("36", palette.separator);
+    }
+
+    #[test]
+    fn parses_color_choice() {
+        assert_eq!(Ok(ColorChoice::Never), "never".parse());
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+        assert!(ColorChoice::Always.should_color());
+    }
+}
@@ -0,0 +1,28 @@
+//! Diagnostics about what a search is doing.
+//!
+//! Standard output is for results only, so that it can be piped into other
+//! tools. Anything else worth telling the user goes to stderr through the
+//! `verbose!` macro, and only when `--verbose` asked for it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Turns verbose diagnostics on or off for the whole process.
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Writes a line to stderr, like `eprintln!`, if verbose diagnostics are on.
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::diag::is_verbose() {
+            eprintln!($($arg)*);
+        }
+    };
+}
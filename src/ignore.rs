@@ -0,0 +1,241 @@
+//! Parses `.gitignore` style files and decides whether a path is ignored.
+//!
+//! Each file's patterns are relative to the directory the file lives in.
+//! The walker keeps one `Gitignore` per ignore file on its way down the
+//! tree, and files in deeper directories take precedence over those above.
+
+use std::{fs, path::Path};
+
+use crate::regex::{self, Regex};
+
+/// The rules from a single ignore file.
+#[derive(Debug, Default)]
+pub struct Gitignore {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    regex: Regex,
+    /// A `!pattern`, which re-includes paths an earlier rule ignored.
+    negated: bool,
+    /// A `pattern/`, which only matches directories.
+    dir_only: bool,
+}
+
+/// What an ignore file has to say about a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Ignore,
+    Include,
+}
+
+impl Gitignore {
+    /// Reads and parses the ignore file at `path`. A missing or unreadable
+    /// file has no rules.
+    pub fn from_file(path: &Path) -> Gitignore {
+        match fs::read_to_string(path) {
+            Ok(contents) => Gitignore::parse(&contents),
+            Err(_) => Gitignore::default(),
+        }
+    }
+
+    /// Parses the contents of an ignore file. Lines that are not valid
+    /// patterns are skipped, the same way git skips them.
+    pub fn parse(contents: &str) -> Gitignore {
+        let rules = contents.lines().filter_map(Rule::parse).collect();
+        Gitignore { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Checks `path`, which must be relative to the directory holding the
+    /// ignore file. The last rule that matches wins, and `None` means no
+    /// rule matched at all.
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<Verdict> {
+        let path = path.to_string_lossy().replace('\\', "/");
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(path.as_bytes()))
+            .map(|rule| {
+                if rule.negated {
+                    Verdict::Include
+                } else {
+                    Verdict::Ignore
+                }
+            })
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let mut pattern = trim_trailing_spaces(line);
+        let negated = pattern.starts_with('!');
+        if negated {
+            pattern = &pattern[1..];
+        }
+        // `\!` and `\#` stand for a literal leading `!` or `#`.
+        if pattern.starts_with("\\!") || pattern.starts_with("\\#") {
+            pattern = &pattern[1..];
+        }
+
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+
+        // A slash anywhere but the end ties the pattern to this directory;
+        // otherwise it may match at any depth.
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+        let regex = Regex::new(&glob_to_regex(pattern, anchored)).ok()?;
+        Some(Rule {
+            regex,
+            negated,
+            dir_only,
+        })
+    }
+}
+
+/// Strips trailing spaces, except for one escaped with a backslash.
+fn trim_trailing_spaces(line: &str) -> &str {
+    let trimmed = line.trim_end_matches(' ');
+    if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+/// Translates a glob into a regular expression matching whole paths.
+fn glob_to_regex(glob: &str, anchored: bool) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut re = String::from("^");
+    if !anchored {
+        re.push_str("(?:.*/)?");
+    }
+
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                let at_end = i + 2 == chars.len();
+                if at_start && at_end {
+                    // `foo/**` matches everything inside foo.
+                    re.push_str(".*");
+                    i += 2;
+                } else if at_start && chars.get(i + 2) == Some(&'/') {
+                    // `**/` matches zero or more directories.
+                    re.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    re.push_str("[^/]*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => match chars[i + 1..].iter().skip(1).position(|&c| c == ']') {
+                Some(len) => {
+                    let class = &chars[i + 1..i + 2 + len];
+                    re.push('[');
+                    for (j, &c) in class.iter().enumerate() {
+                        match c {
+                            '!' | '^' if j == 0 => re.push('^'),
+                            '\\' | '[' | ']' => {
+                                re.push('\\');
+                                re.push(c);
+                            }
+                            c => re.push(c),
+                        }
+                    }
+                    re.push(']');
+                    i += len + 3;
+                    continue;
+                }
+                None => re.push_str("\\["),
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                re.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+
+    re.push('$');
+    re
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verdict(gitignore: &Gitignore, path: &str, is_dir: bool) -> Option<Verdict> {
+        gitignore.matched(Path::new(path), is_dir)
+    }
+
+    #[test]
+    fn unanchored_and_anchored_patterns() {
+        let gitignore = Gitignore::parse("# build output\n*.log\n/target\ndocs/*.html\n");
+
+        assert_eq!(Some(Verdict::Ignore), verdict(&gitignore, "a.log", false));
+        assert_eq!(
+            Some(Verdict::Ignore),
+            verdict(&gitignore, "sub/b.log", false)
+        );
+        assert_eq!(Some(Verdict::Ignore), verdict(&gitignore, "target", true));
+        assert_eq!(None, verdict(&gitignore, "sub/target", true));
+        assert_eq!(
+            Some(Verdict::Ignore),
+            verdict(&gitignore, "docs/x.html", false)
+        );
+        assert_eq!(None, verdict(&gitignore, "docs/api/x.html", false));
+    }
+
+    #[test]
+    fn negation_and_directory_rules() {
+        let gitignore = Gitignore::parse("*.txt\n!keep.txt\nbuild/\n");
+
+        assert_eq!(Some(Verdict::Ignore), verdict(&gitignore, "a.txt", false));
+        assert_eq!(
+            Some(Verdict::Include),
+            verdict(&gitignore, "keep.txt", false)
+        );
+        assert_eq!(
+            Some(Verdict::Ignore),
+            verdict(&gitignore, "src/build", true)
+        );
+        assert_eq!(None, verdict(&gitignore, "src/build", false));
+    }
+
+    #[test]
+    fn double_star_and_classes() {
+        let gitignore = Gitignore::parse("**/gen/**\na/**/z\nfile[0-9].rs\n");
+
+        assert_eq!(
+            Some(Verdict::Ignore),
+            verdict(&gitignore, "x/gen/y.rs", false)
+        );
+        assert_eq!(Some(Verdict::Ignore), verdict(&gitignore, "a/z", false));
+        assert_eq!(Some(Verdict::Ignore), verdict(&gitignore, "a/b/c/z", false));
+        assert_eq!(
+            Some(Verdict::Ignore),
+            verdict(&gitignore, "file7.rs", false)
+        );
+        assert_eq!(None, verdict(&gitignore, "filex.rs", false));
+    }
+}
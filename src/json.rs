@@ -0,0 +1,166 @@
+//! Writes search results as JSON Lines, one object per event.
+//!
+//! Every event looks like `{"type":"match","data":{...}}`. A search of one
+//! input is a `begin` event, then a `match` or `context` event for every
+//! line, then an `end` event with that input's stats. A `summary` event with
+//! the totals comes last.
+//!
+//! Paths and lines are written as `{"text":"..."}` when they are valid
+//! UTF-8, and as `{"bytes":"..."}` holding their base64 encoding when they
+//! are not, so that no input is ever mangled.
+
+use std::{
+    io::{self, Write},
+    ops::Range,
+};
+
+use crate::searcher::Line;
+
+/// Counts of what was found, either in one input or in all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// How many inputs were searched.
+    pub searches: u64,
+    /// How many of those had at least one matching line.
+    pub searches_with_match: u64,
+    pub matched_lines: u64,
+    /// How many matches there were, counting every match in a line.
+    pub matches: u64,
+}
+
+pub(crate) fn begin<W: Write>(wtr: &mut W, path: Option<&[u8]>) -> io::Result<()> {
+    wtr.write_all(br#"{"type":"begin","data":{"path":"#)?;
+    write_path(wtr, path)?;
+    wtr.write_all(b"}}\n")
+}
+
+/// Writes a `match` or `context` event for `line`, whose matches are at
+/// `spans`.
+pub(crate) fn line<W: Write>(
+    wtr: &mut W,
+    kind: &str,
+    path: Option<&[u8]>,
+    line: &Line<'_>,
+    spans: &[Range<usize>],
+) -> io::Result<()> {
+    write!(wtr, r#"{{"type":"{kind}","data":{{"path":"#)?;
+    write_path(wtr, path)?;
+    write!(
+        wtr,
+        r#","line_number":{},"absolute_offset":{},"line":"#,
+        line.number, line.byte_offset
+    )?;
+    write_data(wtr, line.bytes)?;
+    wtr.write_all(br#","submatches":["#)?;
+    for (i, span) in spans.iter().enumerate() {
+        if i > 0 {
+            wtr.write_all(b",")?;
+        }
+        wtr.write_all(br#"{"match":"#)?;
+        write_data(wtr, &line.bytes[span.clone()])?;
+        write!(wtr, r#","start":{},"end":{}}}"#, span.start, span.end)?;
+    }
+    wtr.write_all(b"]}}\n")
+}
+
+pub(crate) fn end<W: Write>(wtr: &mut W, path: Option<&[u8]>, stats: &Stats) -> io::Result<()> {
+    wtr.write_all(br#"{"type":"end","data":{"path":"#)?;
+    write_path(wtr, path)?;
+    write!(
+        wtr,
+        r#","stats":{{"matched_lines":{},"matches":{}}}}}}}"#,
+        stats.matched_lines, stats.matches
+    )?;
+    wtr.write_all(b"\n")
+}
+
+pub(crate) fn summary<W: Write>(wtr: &mut W, stats: &Stats) -> io::Result<()> {
+    writeln!(
+        wtr,
+        r#"{{"type":"summary","data":{{"stats":{{"searches":{},"searches_with_match":{},"matched_lines":{},"matches":{}}}}}}}"#,
+        stats.searches, stats.searches_with_match, stats.matched_lines, stats.matches
+    )
+}
+
+fn write_path<W: Write>(wtr: &mut W, path: Option<&[u8]>) -> io::Result<()> {
+    match path {
+        Some(path) => write_data(wtr, path),
+        None => wtr.write_all(b"null"),
+    }
+}
+
+/// Writes `bytes` as `{"text":...}` or, if they aren't UTF-8, `{"bytes":...}`.
+fn write_data<W: Write>(wtr: &mut W, bytes: &[u8]) -> io::Result<()> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => {
+            wtr.write_all(br#"{"text":"#)?;
+            write_string(wtr, text)?;
+        }
+        Err(_) => write!(wtr, r#"{{"bytes":"{}""#, base64(bytes))?,
+    }
+    wtr.write_all(b"}")
+}
+
+/// Writes `s` as a quoted JSON string.
+fn write_string<W: Write>(wtr: &mut W, s: &str) -> io::Result<()> {
+    wtr.write_all(b"\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => wtr.write_all(b"\\\"")?,
+            '\\' => wtr.write_all(b"\\\\")?,
+            '\n' => wtr.write_all(b"\\n")?,
+            '\r' => wtr.write_all(b"\\r")?,
+            '\t' => wtr.write_all(b"\\t")?,
+            c if (c as u32) < 0x20 => write!(wtr, "\\u{:04x}", c as u32)?,
+            c => wtr.write_all(c.encode_utf8(&mut [0; 4]).as_bytes())?,
+        }
+    }
+    wtr.write_all(b"\"")
+}
+
+/// Encodes `bytes` as standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9v", base64(b"foo"));
+        assert_eq!("/+7/", base64(b"\xff\xee\xff"));
+    }
+
+    #[test]
+    fn writes_text_or_bytes() {
+        let mut out = Vec::new();
+        write_data(&mut out, "a \"tab\"\there\u{1}".as_bytes()).unwrap();
+        write_data(&mut out, b"sun \xff").unwrap();
+
+        assert_eq!(
+            r#"{"text":"a \"tab\"\there\u0001"}{"bytes":"c3VuIP8="}"#,
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
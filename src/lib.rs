@@ -1,51 +1,626 @@
-use std::{env, error::Error, fs};
+use std::{
+    env,
+    error::Error,
+    fs::{self, File},
+    io::{self, BufReader, IsTerminal, Write},
+    iter,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+pub mod aho_corasick;
+pub mod cli;
+pub mod color;
+pub mod diag;
+pub mod ignore;
+pub mod json;
+pub mod matcher;
+pub mod printer;
+pub mod regex;
+pub mod replace;
+pub mod rewrite;
+pub mod searcher;
+#[cfg(test)]
+mod test_util;
+mod utf8;
+pub mod walk;
+
+use aho_corasick::AhoCorasick;
+use cli::Arg;
+use color::{ColorChoice, Palette};
+use matcher::{LineMatcher, LiteralMatcher, Matcher, WordMatcher};
+use printer::{ColumnUnit, Mode, PrintSink, Printer};
+use regex::RegexBuilder;
+use replace::Replacement;
+use rewrite::{Answer, Edit};
+use searcher::Searcher;
+use walk::Walker;
 
 pub struct Config {
-    pub query: String,
-    pub file_path: String,
+    /// What to search for. A line matches if any of these do.
+    pub patterns: Vec<String>,
+    /// The files and directories to search. `-` means standard input.
+    pub paths: Vec<String>,
     pub ignore_case: bool,
+    /// Treat the query as a regular expression instead of a fixed string.
+    pub regex: bool,
+    /// Print the lines that don't match instead of the ones that do.
+    pub invert_match: bool,
+    /// Only match whole words, so that `sun` does not match `sunshine`.
+    pub word_regexp: bool,
+    /// Only match whole lines.
+    pub line_regexp: bool,
+    /// How many lines of context to print before each match.
+    pub before_context: usize,
+    /// How many lines of context to print after each match.
+    pub after_context: usize,
+    /// Whether to print matching lines, just the matches, counts or file
+    /// names.
+    pub mode: Mode,
+    /// Start each line with its line number.
+    pub line_number: bool,
+    /// Start each line with the column of its first match, counting from 1.
+    pub column: bool,
+    /// Whether columns count bytes or chars.
+    pub column_unit: ColumnUnit,
+    /// Start each line with its byte offset in the file.
+    pub byte_offset: bool,
+    /// Print matching lines with their matches replaced by this.
+    pub replace: Option<String>,
+    /// Write the replacements back to the files instead of printing them.
+    pub in_place: bool,
+    /// Keep a copy of each rewritten file, with `.bak` added to its name.
+    pub backup: bool,
+    /// Print the changes `in_place` would make as a diff, without making
+    /// them.
+    pub dry_run: bool,
+    /// Ask before making each replacement with `in_place`.
+    pub interactive: bool,
+    /// When to highlight matches with colors.
+    pub color: ColorChoice,
+    /// Flush the output after every line. This is always done when stdout
+    /// is a terminal.
+    pub line_buffered: bool,
+    /// Whether to start each line with the path it came from. When `None`,
+    /// paths are printed if there is more than one file to search.
+    pub with_filename: Option<bool>,
+    /// How to walk the paths that are directories.
+    pub walker: Walker,
+    /// Describe what is being searched on stderr.
+    pub verbose: bool,
 }
 
 impl Config {
-    pub fn build(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
-            return Err("not enough arguments");
+    /// Builds a config from the program's arguments, `args[0]` being the
+    /// program name. Environment variables provide the defaults, and
+    /// command-line options override them.
+    pub fn build(args: &[String]) -> Result<Config, cli::Error> {
+        Config::build_with(args, |name| env::var(name).ok())
+    }
+
+    /// Does the work of `build`, with `var` looking up the environment.
+    fn build_with(
+        args: &[String],
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, cli::Error> {
+        // Defaults come from the environment: `MINIGREP_IGNORE_CASE=true`
+        // is the same as starting the arguments with `--ignore-case`, and so
+        // on for every option. The old IGNORE_CASE still works too, but its
+        // value is read now, rather than just whether it is set, so
+        // `IGNORE_CASE=0` turns case-insensitivity off.
+        let given = cli::parse(args.get(1..).unwrap_or_default())?;
+        let defaults = cli::env_args(&given, var)?;
+
+        let mut ignore_case = false;
+        let mut regex = false;
+        let mut invert_match = false;
+        let mut context = None;
+        let mut before_context = None;
+        let mut after_context = None;
+        let mut color = ColorChoice::Auto;
+        let mut word_regexp = false;
+        let mut line_regexp = false;
+        let mut mode = Mode::Lines;
+        let mut line_number = false;
+        let mut column = false;
+        let mut column_unit = ColumnUnit::Bytes;
+        let mut byte_offset = false;
+        let mut replace = None;
+        let mut in_place = false;
+        let mut backup = false;
+        let mut dry_run = false;
+        let mut interactive = false;
+        let mut line_buffered = false;
+        let mut with_filename = None;
+        let mut verbose = false;
+        let mut walker = Walker::default();
+
+        // -i and -s, whichever came last, win over smart case.
+        let mut case_given = None;
+        let mut smart_case = false;
+        let mut patterns = Vec::new();
+        let mut pattern_given = false;
+        let mut positionals = Vec::new();
+        for arg in defaults.into_iter().chain(given) {
+            let (opt, value) = match arg {
+                Arg::Positional(arg) => {
+                    positionals.push(arg);
+                    continue;
+                }
+                Arg::Opt(opt, value) => (opt, value),
+            };
+
+            match opt.long {
+                "regexp" => {
+                    patterns.push(value.unwrap_or_default());
+                    pattern_given = true;
+                }
+                "file" => {
+                    let file = value.unwrap_or_default();
+                    let contents = fs::read_to_string(&file)
+                        .map_err(|err| cli::Error::Usage(format!("{file}: {err}")))?;
+                    patterns.extend(contents.lines().map(String::from));
+                    pattern_given = true;
+                }
+                "ignore-case" => case_given = Some(true),
+                "case-sensitive" => case_given = Some(false),
+                "smart-case" => smart_case = true,
+                "regex" => regex = true,
+                "fixed-strings" => regex = false,
+                "invert-match" => invert_match = true,
+                "word-regexp" => word_regexp = true,
+                "line-regexp" => line_regexp = true,
+                "after-context" => after_context = Some(cli::number(opt, value)?),
+                "before-context" => before_context = Some(cli::number(opt, value)?),
+                "context" => context = Some(cli::number(opt, value)?),
+                "count" => mode = Mode::Count,
+                "files-with-matches" => mode = Mode::FilesWithMatches,
+                "files-without-match" => mode = Mode::FilesWithoutMatch,
+                "quiet" => mode = Mode::Quiet,
+                "json" => mode = Mode::Json,
+                "only-matching" => mode = Mode::OnlyMatching,
+                "line-number" => line_number = true,
+                "column" => column = true,
+                "column-unit" => column_unit = value.unwrap_or_default().parse()?,
+                "byte-offset" => byte_offset = true,
+                "replace" => replace = value,
+                "in-place" => in_place = true,
+                "backup" => backup = true,
+                "dry-run" => dry_run = true,
+                "interactive" => interactive = true,
+                "color" => color = value.unwrap_or_default().parse()?,
+                "line-buffered" => line_buffered = true,
+                "max-depth" => walker.max_depth = Some(cli::number(opt, value)?),
+                "hidden" => walker.hidden = true,
+                "follow" => walker.follow_links = true,
+                "no-ignore" => walker.ignore_files = false,
+                "with-filename" => with_filename = Some(true),
+                "no-filename" => with_filename = Some(false),
+                "verbose" => verbose = true,
+                "help" => return Err(cli::Error::Help),
+                "version" => return Err(cli::Error::Version),
+                name => unreachable!("option --{name} is not handled"),
+            }
         }
 
-        let query = args[1].clone();
-        let file_path = args[2].clone();
+        // Without -e or -f, the first argument is the pattern.
+        if !pattern_given {
+            if positionals.is_empty() {
+                return Err(cli::Error::from("not enough arguments"));
+            }
+            patterns.push(positionals.remove(0));
+        }
+        // With smart case, a pattern in lowercase matches any case, and one
+        // with an uppercase letter in it only matches exactly.
+        ignore_case = match case_given {
+            Some(ignore_case) => ignore_case,
+            None if smart_case => !patterns.iter().any(|p| has_uppercase(p, regex)),
+            None => ignore_case,
+        };
 
-        // We’re using the is_ok method on the Result to check whether the environment variable is set,
-        // which means the program should do a case-insensitive search.
-        // If the IGNORE_CASE environment variable isn’t set to anything,
-        // is_ok will return false and the program will perform a case-sensitive search.
-        // We don’t care about the value of the environment variable, just whether it’s set or unset,
-        // so we’re checking is_ok rather than using unwrap, expect,
-        // or any of the other methods we’ve seen on Result.
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
+        // A dry run is an in-place rewrite which doesn't get written, and
+        // an interactive one asks about each replacement first.
+        in_place |= dry_run || interactive;
+        if in_place && replace.is_none() {
+            return Err(cli::Error::from(
+                "--in-place, --interactive and --dry-run need --replace",
+            ));
+        }
+        // Rewriting always replaces every match in the matching lines, so
+        // options that pick other lines or other output make no sense.
+        if in_place && (invert_match || mode != Mode::Lines) {
+            return Err(cli::Error::from(
+                "--in-place, --interactive and --dry-run can't be used with \
+                 -v, -o, -c, -l, -L, -q or --json",
+            ));
+        }
+        // Without a path we read from standard input, just like `-`.
+        let mut paths = positionals;
+        if paths.is_empty() {
+            paths.push(String::from("-"));
+        }
 
         Ok(Config {
-            query,
-            file_path,
+            patterns,
+            paths,
             ignore_case,
+            regex,
+            invert_match,
+            word_regexp,
+            line_regexp,
+            // -A and -B win over -C, whichever order they were given in.
+            before_context: before_context.or(context).unwrap_or(0),
+            after_context: after_context.or(context).unwrap_or(0),
+            mode,
+            line_number,
+            column,
+            column_unit,
+            byte_offset,
+            replace,
+            in_place,
+            backup,
+            dry_run,
+            interactive,
+            color,
+            line_buffered,
+            with_filename,
+            walker,
+            verbose,
         })
     }
+
+    /// Builds the matcher described by this config: a regular expression
+    /// when `regex` is set, and a plain substring search otherwise. Several
+    /// fixed strings are searched for all at once with Aho-Corasick, and
+    /// several regular expressions are joined into one alternation, as are
+    /// several fixed strings with `word_regexp`.
+    pub fn matcher(&self) -> Result<Box<dyn Matcher>, regex::Error> {
+        // Aho-Corasick only reports the longest of the fixed strings that
+        // start at a spot, which may not be a whole word when a shorter one
+        // is, so with -w they are escaped and searched for as a regex.
+        let regex =
+            self.regex || (self.word_regexp && !self.line_regexp && self.patterns.len() > 1);
+        let matcher: Box<dyn Matcher> = match self.patterns.as_slice() {
+            [pattern] if !regex => Box::new(LiteralMatcher::new(pattern, self.ignore_case)),
+            // An empty `-f` file leaves no patterns, which matches nothing.
+            patterns if !regex || patterns.is_empty() => {
+                Box::new(AhoCorasick::new(patterns, self.ignore_case))
+            }
+            patterns => {
+                let patterns: Vec<String> = if self.regex {
+                    patterns.to_vec()
+                } else {
+                    patterns
+                        .iter()
+                        .map(|pattern| regex::escape(pattern))
+                        .collect()
+                };
+                let mut pattern = match patterns.as_slice() {
+                    [pattern] => pattern.clone(),
+                    patterns => patterns
+                        .iter()
+                        .map(|pattern| format!("(?:{pattern})"))
+                        .collect::<Vec<_>>()
+                        .join("|"),
+                };
+                // Anchoring the pattern itself lets every alternative have
+                // its go, so that `ab|abc` matches the whole word `abc`.
+                if self.line_regexp {
+                    pattern = format!("^(?:{pattern})$");
+                } else if self.word_regexp {
+                    pattern = format!(r"\b{{start-half}}(?:{pattern})\b{{end-half}}");
+                }
+                let regex = RegexBuilder::new(&pattern)
+                    .case_insensitive(self.ignore_case)
+                    .build()?;
+                Box::new(regex)
+            }
+        };
+
+        // Like grep, -x wins over -w, since a whole line is also made of
+        // whole words. Regular expressions have already been anchored.
+        if regex {
+            Ok(matcher)
+        } else if self.line_regexp {
+            Ok(Box::new(LineMatcher::new(matcher)))
+        } else if self.word_regexp {
+            Ok(Box::new(WordMatcher::new(matcher)))
+        } else {
+            Ok(matcher)
+        }
+    }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.file_path)?;
+/// Whether `pattern` has an uppercase letter in it. In a regular
+/// expression, escapes like `\W` or `\S` don't count.
+fn has_uppercase(pattern: &str, regex: bool) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if regex && c == '\\' {
+            chars.next();
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Runs the search described by `config`, writing the results to `out`,
+/// and returns whether any line matched. Inputs that can't be searched are
+/// reported on stderr as they come up, and turn the whole run into an error
+/// at the end. Errors writing to `out` end the run right away.
+///
+/// `out` should be buffered; it is flushed before returning.
+pub fn run<W: Write>(config: Config, out: W) -> Result<bool, Box<dyn Error>> {
+    diag::set_verbose(config.verbose);
+    verbose!("Searching for {}", config.patterns.join(" or "));
 
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
-    } else {
-        search(&config.query, &contents)
+    let matcher = config.matcher()?;
+    if config.in_place {
+        // `Config::build` makes sure there is a replacement.
+        let replacement =
+            Replacement::new(config.replace.as_deref().unwrap_or_default(), config.regex);
+        return rewrite_files(&config, &matcher, &replacement, out);
+    }
+
+    // Context only makes sense when the lines themselves are printed.
+    let (before_context, after_context) = match config.mode {
+        Mode::Lines | Mode::Json => (config.before_context, config.after_context),
+        _ => (0, 0),
+    };
+    let searcher = Searcher {
+        invert_match: config.invert_match,
+        before_context,
+        after_context,
+        ..Searcher::default()
     };
+    let printer = Printer {
+        mode: config.mode,
+        line_number: config.line_number,
+        column: config.column,
+        column_unit: config.column_unit,
+        byte_offset: config.byte_offset,
+        replace: config
+            .replace
+            .as_deref()
+            .map(|text| Replacement::new(text, config.regex)),
+        group_separator: before_context > 0 || after_context > 0,
+        // JSON is for programs, which don't want escape codes in it.
+        colors: (config.mode != Mode::Json && config.color.should_color()).then(Palette::from_env),
+        line_buffered: config.line_buffered || io::stdout().is_terminal(),
+    };
+    let mut sink = printer.sink(&matcher, out);
 
-    for line in results {
-        println!("{line}");
+    // A directory counts as more than one file, like with grep -r. When
+    // only file names are printed, there is nothing else to print.
+    let with_filename = match config.mode {
+        Mode::FilesWithMatches | Mode::FilesWithoutMatch | Mode::Json => true,
+        Mode::Lines | Mode::OnlyMatching | Mode::Count | Mode::Quiet => {
+            config.with_filename.unwrap_or_else(|| {
+                config.paths.len() > 1 || config.paths.iter().any(|path| Path::new(path).is_dir())
+            })
+        }
+    };
+
+    // One bad file shouldn't stop the others, so errors are reported as we
+    // go, and we only give up at the very end.
+    let mut failures = 0;
+    let mut report = |err: io::Error, sink: &PrintSink<'_, W>| {
+        // There's no point going on once the output is gone.
+        if sink.write_failed() {
+            return Err(err);
+        }
+        eprintln!("minigrep: {err}");
+        failures += 1;
+        Ok(())
+    };
+
+    // In quiet mode, the first match settles the exit status, even if an
+    // earlier input could not be searched.
+    let quiet = config.mode == Mode::Quiet;
+
+    // Binary files are skipped when they are found by walking a directory.
+    let walk_searcher = Searcher {
+        skip_binary: true,
+        ..searcher.clone()
+    };
+
+    for input in inputs(&config) {
+        let result = input.and_then(|input| match input {
+            // Standard input is searched as it comes in, so with line
+            // buffering every matching line is printed right away rather
+            // than when the pipe closes.
+            Input::Stdin => {
+                verbose!("In standard input");
+                sink.begin(with_filename.then_some(b"(standard input)"))?;
+                searcher.search_reader(&matcher, io::stdin().lock(), &mut sink)?;
+                sink.finish()
+            }
+            Input::File { path, walked } => {
+                let searcher = if walked { &walk_searcher } else { &searcher };
+                search_file(searcher, &matcher, &path, with_filename, &mut sink)
+            }
+        });
+        if let Err(err) = result {
+            report(err, &sink)?;
+        }
+
+        if quiet && sink.has_match() {
+            break;
+        }
+    }
+
+    sink.summarize()?;
+    sink.flush()?;
+    if quiet && sink.has_match() {
+        return Ok(true);
+    }
+    match failures {
+        0 => Ok(sink.has_match()),
+        1 => Err("1 input could not be searched".into()),
+        n => Err(format!("{n} inputs could not be searched").into()),
     }
+}
+
+/// Something to search.
+enum Input {
+    Stdin,
+    /// A file, which was either given as an argument or found by walking a
+    /// directory.
+    File {
+        path: PathBuf,
+        walked: bool,
+    },
+}
 
-    Ok(())
+/// Lists everything `config` says to search, walking into directories.
+fn inputs(config: &Config) -> impl Iterator<Item = io::Result<Input>> + '_ {
+    config
+        .paths
+        .iter()
+        .flat_map(|path| -> Box<dyn Iterator<Item = _>> {
+            if path == "-" {
+                Box::new(iter::once(Ok(Input::Stdin)))
+            } else if !Path::new(path).is_dir() {
+                let path = PathBuf::from(path);
+                Box::new(iter::once(Ok(Input::File {
+                    path,
+                    walked: false,
+                })))
+            } else {
+                verbose!("In directory {path}");
+                let files = config.walker.walk(Path::new(path));
+                Box::new(files.map(|file| file.map(|path| Input::File { path, walked: true })))
+            }
+        })
+}
+
+/// Rewrites every file `config` says to search with its matches replaced,
+/// or just prints the changes as a diff for a dry run. Returns whether any
+/// replacements were made.
+fn rewrite_files<W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    replacement: &Replacement,
+    mut out: W,
+) -> Result<bool, Box<dyn Error>> {
+    let mut failures = 0;
+    let mut replaced_any = false;
+    // An answer of all or quit holds for the rest of the files too.
+    let mut decided = None;
+    let context = match config.before_context.max(config.after_context) {
+        0 => 2,
+        context => context,
+    };
+
+    for input in inputs(config) {
+        if decided == Some(Answer::Quit) {
+            break;
+        }
+        let (path, walked) = match input {
+            Ok(Input::File { path, walked }) => (path, walked),
+            Ok(Input::Stdin) => {
+                eprintln!("minigrep: standard input can't be rewritten");
+                failures += 1;
+                continue;
+            }
+            Err(err) => {
+                eprintln!("minigrep: {err}");
+                failures += 1;
+                continue;
+            }
+        };
+
+        verbose!("In file {}", path.display());
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("minigrep: {}", walk::with_path(err, &path));
+                failures += 1;
+                continue;
+            }
+        };
+        // Binary files found by walking are skipped, as when searching.
+        if walked && contents.contains(&0) {
+            continue;
+        }
+
+        let name = path.display().to_string();
+        let (new, count) = if config.interactive {
+            rewrite::replace_lines_with(matcher, replacement, &contents, |edit| {
+                if let Some(answer) = decided {
+                    return Ok(answer);
+                }
+                let answer = ask(&name, edit, context)?;
+                if matches!(answer, Answer::All | Answer::Quit) {
+                    decided = Some(answer);
+                }
+                Ok(answer)
+            })?
+        } else {
+            rewrite::replace_lines(matcher, replacement, &contents)
+        };
+        if count == 0 {
+            continue;
+        }
+        replaced_any = true;
+
+        if config.dry_run {
+            rewrite::write_diff(&mut out, &name, &contents, &new)?;
+        } else if let Err(err) = rewrite::write_atomically(&path, &new, config.backup) {
+            eprintln!("minigrep: {}", walk::with_path(err, &path));
+            failures += 1;
+        } else {
+            let plural = if count == 1 { "" } else { "s" };
+            writeln!(out, "{name}: {count} replacement{plural}")?;
+        }
+    }
+
+    out.flush()?;
+    match failures {
+        0 => Ok(replaced_any),
+        1 => Err("1 input could not be rewritten".into()),
+        n => Err(format!("{n} inputs could not be rewritten").into()),
+    }
+}
+
+/// Shows `edit` on stderr and asks whether to make it, until the answer
+/// makes sense.
+fn ask(path: &str, edit: &Edit<'_>, context: usize) -> io::Result<Answer> {
+    let mut stderr = io::stderr().lock();
+    rewrite::write_edit(&mut stderr, path, edit, context)?;
+    loop {
+        write!(stderr, "Replace? [y]es, [n]o, [a]ll, [q]uit: ")?;
+        stderr.flush()?;
+        let mut answer = String::new();
+        // Running out of answers is as good as quitting.
+        if io::stdin().read_line(&mut answer)? == 0 {
+            return Ok(Answer::Quit);
+        }
+        match answer.trim().parse() {
+            Ok(answer) => return Ok(answer),
+            Err(msg) => writeln!(stderr, "{msg}")?,
+        }
+    }
+}
+
+fn search_file<W: io::Write>(
+    searcher: &Searcher,
+    matcher: &dyn Matcher,
+    path: &Path,
+    with_filename: bool,
+    sink: &mut PrintSink<'_, W>,
+) -> io::Result<()> {
+    verbose!("In file {}", path.display());
+    sink.begin(with_filename.then_some(path.as_os_str().as_encoded_bytes()))?;
+    let result = File::open(path)
+        .and_then(|file| searcher.search_reader(matcher, BufReader::new(file), sink));
+    match result {
+        // Only errors reading the file are about the file.
+        Err(err) if !sink.write_failed() => Err(walk::with_path(err, path)),
+        Err(err) => Err(err),
+        Ok(()) => sink.finish(),
+    }
 }
 
 /// Search function:
@@ -69,10 +644,22 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
 /// 4. If it doesn’t, do nothing.
 /// 5. Return the list of results that match.
 pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    search_with(&LiteralMatcher::new(query, false), contents)
+}
+
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    // The matcher folds the case of each char as it compares them,
+    // rather than lowercasing whole lines up front.
+    search_with(&LiteralMatcher::new(query, true), contents)
+}
+
+/// Same as `search`, but lets the caller decide how a line is matched.
+/// Any `Matcher` works here, for example a `regex::Regex`.
+pub fn search_with<'a, M: Matcher + ?Sized>(matcher: &M, contents: &'a str) -> Vec<&'a str> {
     let mut results = Vec::new();
 
     for line in contents.lines() {
-        if line.contains(query) {
+        if matcher.is_match(line.as_bytes()) {
             results.push(line);
         }
     }
@@ -80,15 +667,12 @@ pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
     results
 }
 
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    // we lowercase the query string and store it in a shadowed variable with the same name
-    // Note that query is now a String rather than a string slice,
-    // because calling to_lowercase creates new data rather than referencing existing data
-    let query = query.to_lowercase();
+/// The opposite of `search_with`: returns the lines that do *not* match.
+pub fn search_inverted<'a, M: Matcher + ?Sized>(matcher: &M, contents: &'a str) -> Vec<&'a str> {
     let mut results = Vec::new();
 
     for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
+        if !matcher.is_match(line.as_bytes()) {
             results.push(line);
         }
     }
@@ -96,9 +680,70 @@ pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a st
     results
 }
 
+/// A line that matched, along with where it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'a> {
+    /// The line number, starting from 1.
+    pub line_number: usize,
+    /// The offset of the first byte of the line in the searched contents.
+    pub byte_offset: usize,
+    /// The line itself, without its line terminator.
+    pub line: &'a str,
+    /// The byte ranges of every match within `line`, from left to right.
+    pub spans: Vec<Range<usize>>,
+}
+
+/// Like `search_with`, but instead of just the lines it returns a `Match`
+/// describing where each line is and where in it the matches are.
+pub fn search_matches<'a, M: Matcher + ?Sized>(matcher: &M, contents: &'a str) -> Vec<Match<'a>> {
+    let mut results = Vec::new();
+    let mut byte_offset = 0;
+
+    for (index, raw) in contents.split_inclusive('\n').enumerate() {
+        // Strip the terminator the same way `str::lines` does.
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        let spans = matcher.find_all(line.as_bytes());
+        if !spans.is_empty() {
+            results.push(Match {
+                line_number: index + 1,
+                byte_offset,
+                line,
+                spans,
+            });
+        }
+
+        byte_offset += raw.len();
+    }
+
+    results
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{regex::Regex, test_util::scratch_dir};
+
+    /// Runs minigrep with `args` followed by `paths`, which are relative to
+    /// `dir`, and returns its result and output, with `dir` taken out of
+    /// the paths.
+    fn run_in(dir: &Path, args: &[&str], paths: &[&str]) -> (Result<bool, String>, String) {
+        let args: Vec<String> = ["minigrep", "--color=never"]
+            .iter()
+            .chain(args)
+            .map(|arg| arg.to_string())
+            .chain(
+                paths
+                    .iter()
+                    .map(|path| dir.join(path).display().to_string()),
+            )
+            .collect();
+        let mut out = Vec::new();
+        let result = run(Config::build(&args).unwrap(), &mut out).map_err(|err| err.to_string());
+        let prefix = format!("{}{}", dir.display(), std::path::MAIN_SEPARATOR);
+        (result, String::from_utf8(out).unwrap().replace(&prefix, ""))
+    }
 
     #[test]
     fn case_sensitive() {
@@ -129,4 +774,315 @@ to the length of their dreams.";
             search_case_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn regex_search() {
+        let matcher = Regex::new(r"^\w+ (is|and) ").unwrap();
+        let contents = "\
+iDEATH is a place where the sun shines
+a different colour every day
+and where people travel
+to the length of their dreams.";
+
+        assert_eq!(
+            vec!["iDEATH is a place where the sun shines"],
+            search_with(&matcher, contents)
+        );
+    }
+
+    #[test]
+    fn inverted_search() {
+        let matcher = LiteralMatcher::new("THE", true);
+        let contents = "\
+iDEATH is a place where the sun shines
+a different colour every day
+and where people travel
+to the length of their dreams.";
+
+        assert_eq!(
+            vec!["a different colour every day", "and where people travel"],
+            search_inverted(&matcher, contents)
+        );
+    }
+
+    #[test]
+    fn missing_path_reads_stdin() {
+        let args = vec![String::from("minigrep"), String::from("sun")];
+        let config = Config::build(&args).unwrap();
+
+        assert_eq!(vec!["-"], config.paths);
+        assert!(Config::build(&args[..1]).is_err());
+    }
+
+    #[test]
+    fn flags_override_defaults() {
+        let args: Vec<String> = [
+            "minigrep",
+            "-iE",
+            "-A1",
+            "--context=2",
+            "--",
+            "-sun",
+            "poem.txt",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        let config = Config::build(&args).unwrap();
+
+        assert_eq!(vec!["-sun"], config.patterns);
+        assert_eq!(vec!["poem.txt"], config.paths);
+        assert!(config.ignore_case && config.regex);
+        assert_eq!((2, 1), (config.before_context, config.after_context));
+
+        let args = vec![String::from("minigrep"), String::from("--help")];
+        assert_eq!(Some(cli::Error::Help), Config::build(&args).err());
+    }
+
+    #[test]
+    fn file_name_prefixes() {
+        let dir = scratch_dir("names", &[("a.txt", "sun\nmoon\n"), ("b.txt", "the sun\n")]);
+
+        assert_eq!(
+            (Ok(true), String::from("a.txt:sun\nb.txt:the sun\n")),
+            run_in(&dir, &["sun"], &["a.txt", "b.txt"])
+        );
+        assert_eq!(
+            (Ok(true), String::from("sun\nthe sun\n")),
+            run_in(&dir, &["--no-filename", "sun"], &["a.txt", "b.txt"])
+        );
+        assert_eq!(
+            (Ok(true), String::from("a.txt:sun\n")),
+            run_in(&dir, &["-H", "sun"], &["a.txt"])
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn carries_on_past_missing_files() {
+        let dir = scratch_dir("missing", &[("a.txt", "sun\n"), ("b.txt", "the sun\n")]);
+
+        assert_eq!(
+            (
+                Err(String::from("1 input could not be searched")),
+                String::from("a.txt:sun\nb.txt:the sun\n")
+            ),
+            run_in(&dir, &["sun"], &["a.txt", "missing.txt", "b.txt"])
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn match_status_and_quiet_mode() {
+        let dir = scratch_dir("status", &[("a.txt", "sun\n"), ("b.txt", "moon\n")]);
+
+        // `main` turns these into exit codes 0, 1 and 2.
+        assert_eq!(Ok(true), run_in(&dir, &["sun"], &["a.txt"]).0);
+        assert_eq!(Ok(false), run_in(&dir, &["sun"], &["b.txt"]).0);
+        assert!(run_in(&dir, &["sun"], &["missing.txt"]).0.is_err());
+
+        // Quiet mode prints nothing, and a match wins over an error, even
+        // one that came first.
+        assert_eq!(
+            (Ok(true), String::new()),
+            run_in(&dir, &["-q", "sun"], &["missing.txt", "a.txt", "b.txt"])
+        );
+        assert_eq!(
+            (Ok(false), String::new()),
+            run_in(&dir, &["-q", "sun"], &["b.txt"])
+        );
+        assert!(run_in(&dir, &["-q", "sun"], &["missing.txt", "b.txt"])
+            .0
+            .is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn output_holds_only_results() {
+        let dir = scratch_dir("banner", &[("poem.txt", "sun\nmoon\n")]);
+
+        // The banner goes to stderr, with or without --verbose.
+        for args in [&["sun"][..], &["--verbose", "sun"]] {
+            assert_eq!(
+                (Ok(true), String::from("sun\n")),
+                run_in(&dir, args, &["poem.txt"])
+            );
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rewriting_rejects_other_modes() {
+        let build = |args: &[&str]| {
+            let args: Vec<String> = ["minigrep", "-r", "moon", "sun", "poem.txt"]
+                .iter()
+                .chain(args)
+                .map(|arg| arg.to_string())
+                .collect();
+            Config::build(&args)
+        };
+
+        assert!(build(&["--in-place"]).is_ok());
+        for flag in ["-v", "-o", "-c", "-l", "-L", "-q", "--json"] {
+            assert!(build(&["--in-place", flag]).is_err());
+            assert!(build(&["--dry-run", flag]).is_err());
+        }
+    }
+
+    #[test]
+    fn options_from_the_environment() {
+        let args: Vec<String> = ["minigrep", "sun", "poem.txt"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let var = |name: &str| match name {
+            "MINIGREP_REGEXP" => Some(String::from("moon")),
+            "MINIGREP_FILE" => Some(String::from("patterns.txt")),
+            "MINIGREP_LINE_NUMBER" => Some(String::from("true")),
+            _ => None,
+        };
+        let config = Config::build_with(&args, var).unwrap();
+
+        // Patterns never come from the environment, so QUERY stays QUERY.
+        assert_eq!(vec!["sun"], config.patterns);
+        assert_eq!(vec!["poem.txt"], config.paths);
+        assert!(config.line_number);
+    }
+
+    #[test]
+    fn multiple_patterns() {
+        let args: Vec<String> = ["minigrep", "-e", "sun", "--regexp=d[a-z]y", "poem.txt"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let mut config = Config::build(&args).unwrap();
+        let contents = "\
+iDEATH is a place where the sun shines
+a different colour every day
+and where people travel
+to the length of their dreams.";
+
+        assert_eq!(vec!["poem.txt"], config.paths);
+        assert_eq!(
+            vec!["iDEATH is a place where the sun shines"],
+            search_with(&config.matcher().unwrap(), contents)
+        );
+
+        config.regex = true;
+        assert_eq!(
+            vec![
+                "iDEATH is a place where the sun shines",
+                "a different colour every day"
+            ],
+            search_with(&config.matcher().unwrap(), contents)
+        );
+    }
+
+    #[test]
+    fn whole_words_and_lines() {
+        let args = vec![
+            String::from("minigrep"),
+            String::from("-iw"),
+            String::from("SUN"),
+        ];
+        let mut config = Config::build(&args).unwrap();
+        let contents = "\
+Sun
+sunshine
+the sun shines
+sunny";
+
+        assert_eq!(
+            vec!["Sun", "the sun shines"],
+            search_with(&config.matcher().unwrap(), contents)
+        );
+
+        config.line_regexp = true;
+        assert_eq!(
+            vec!["Sun"],
+            search_with(&config.matcher().unwrap(), contents)
+        );
+
+        config.regex = true;
+        config.patterns = vec![String::from("s|sun")];
+        assert_eq!(
+            vec!["Sun"],
+            search_with(&config.matcher().unwrap(), contents)
+        );
+
+        config.line_regexp = false;
+        config.patterns = vec![String::from(r"sun\w*")];
+        assert_eq!(
+            contents.lines().collect::<Vec<_>>(),
+            search_with(&config.matcher().unwrap(), contents)
+        );
+
+        // Every alternative is tried, not just the leftmost-first one.
+        config.patterns = vec![String::from("su|sunny")];
+        assert_eq!(
+            vec!["sunny"],
+            search_with(&config.matcher().unwrap(), contents)
+        );
+
+        // The same goes for several fixed strings.
+        config.regex = false;
+        config.patterns = vec![String::from("foo"), String::from("foo-bar")];
+        assert_eq!(
+            vec!["foo-barx"],
+            search_with(&config.matcher().unwrap(), "foo-barx\nfoobar")
+        );
+    }
+
+    #[test]
+    fn smart_case() {
+        let build = |args: &[&str]| {
+            let args: Vec<String> = ["minigrep"]
+                .iter()
+                .chain(args)
+                .map(|arg| arg.to_string())
+                .collect();
+            Config::build(&args).unwrap().ignore_case
+        };
+
+        assert!(build(&["-S", "sun"]));
+        assert!(!build(&["-S", "Sun"]));
+        assert!(!build(&["-S", "-e", "sun", "-e", "Moon"]));
+        assert!(build(&["-SE", r"\Wsun\S"]));
+        assert!(!build(&["-S", r"\Wsun\S"]));
+        assert!(build(&["-i", "-S", "Sun"]));
+        assert!(!build(&["-S", "-s", "sun"]));
+        assert!(!build(&["-i", "-s", "sun"]));
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn match_locations() {
+        let matcher = LiteralMatcher::new("the", true);
+        let contents = "\
+iDEATH is a place where the sun shines
+a different colour every day\r
+The length of their dreams.";
+
+        assert_eq!(
+            vec![
+                Match {
+                    line_number: 1,
+                    byte_offset: 0,
+                    line: "iDEATH is a place where the sun shines",
+                    spans: vec![24..27],
+                },
+                Match {
+                    line_number: 3,
+                    byte_offset: 69,
+                    line: "The length of their dreams.",
+                    spans: vec![0..3, 14..17],
+                },
+            ],
+            search_matches(&matcher, contents)
+        );
+    }
 }
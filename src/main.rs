@@ -1,20 +1,50 @@
-use std::{env, process};
+use std::{
+    env,
+    error::Error,
+    io::{self, BufWriter, Write},
+    process,
+};
 
-use minigrep::{run, Config};
+use minigrep::{cli, run, Config};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    let config = Config::build(&args).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {err}");
-        process::exit(1);
+    let config = Config::build(&args).unwrap_or_else(|err| match err {
+        cli::Error::Help | cli::Error::Version => {
+            // `println!` would panic if the reader went away, as with
+            // `minigrep --help | head`, and that's fine too.
+            let mut stdout = io::stdout().lock();
+            match writeln!(stdout, "{}", err.to_string().trim_end()).and_then(|()| stdout.flush()) {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                    eprintln!("Application error: {e}");
+                    process::exit(2);
+                }
+                _ => process::exit(0),
+            }
+        }
+        cli::Error::Usage(msg) => {
+            eprintln!("Problem parsing arguments: {msg}");
+            eprintln!("Try 'minigrep --help' for more information.");
+            process::exit(2);
+        }
     });
 
-    println!("Searching for {}", config.query);
-    println!("In file {}", config.file_path);
+    // Like grep: 0 if something matched, 1 if nothing did, 2 on errors.
+    let stdout = io::stdout().lock();
+    match run(config, BufWriter::new(stdout)) {
+        Ok(true) => process::exit(0),
+        Ok(false) => process::exit(1),
+        // Whoever was reading our output has seen enough, as with `| head`.
+        Err(e) if is_broken_pipe(e.as_ref()) => process::exit(0),
+        Err(e) => {
+            eprintln!("Application error: {e}");
+            process::exit(2);
+        }
+    }
+}
 
-    if let Err(e) = run(config) {
-        eprintln!("Application error: {e}");
-        process::exit(1);
-    };
+fn is_broken_pipe(err: &(dyn Error + 'static)) -> bool {
+    err.downcast_ref::<io::Error>()
+        .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe)
 }
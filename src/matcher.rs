@@ -0,0 +1,304 @@
+//! The `Matcher` trait decides where a query matches inside a line. The
+//! searching and printing code only ever talks to a `Matcher`, so a plain
+//! substring search and a regular expression are interchangeable.
+
+use std::ops::Range;
+
+use crate::{aho_corasick::AhoCorasick, regex::Regex, utf8};
+
+/// Finds matches of a query inside a haystack of bytes.
+///
+/// Haystacks are bytes rather than `&str` so that input which is not valid
+/// UTF-8 can still be searched; invalid bytes simply never match.
+pub trait Matcher {
+    /// Finds the first match that starts at or after `start`.
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>>;
+
+    fn is_match(&self, haystack: &[u8]) -> bool {
+        self.find_at(haystack, 0).is_some()
+    }
+
+    /// Returns every non-overlapping match, from left to right. An empty
+    /// match right where the previous match ended is skipped, so that `x*`
+    /// finds `0..0` and `1..2` in `ax`, but not `2..2` as well.
+    fn find_all(&self, haystack: &[u8]) -> Vec<Range<usize>> {
+        let mut matches: Vec<Range<usize>> = Vec::new();
+        let mut start = 0;
+        while start <= haystack.len() {
+            let m = match self.find_at(haystack, start) {
+                Some(m) => m,
+                None => break,
+            };
+            start = next_start(haystack, &m);
+            if !follows(matches.last(), &m) {
+                matches.push(m);
+            }
+        }
+        matches
+    }
+
+    /// Like `find_at`, but also reports where each capture group matched.
+    /// Group 0 is the whole match, and is the only group of a matcher that
+    /// has no others. Groups which did not take part in the match are
+    /// `None`.
+    fn captures_at(&self, haystack: &[u8], start: usize) -> Option<Captures> {
+        self.find_at(haystack, start).map(|m| vec![Some(m)])
+    }
+
+    /// Returns the captures of every non-overlapping match, from left to
+    /// right, skipping empty matches the same way as `find_all`.
+    fn captures_all(&self, haystack: &[u8]) -> Vec<Captures> {
+        let mut matches: Vec<Captures> = Vec::new();
+        let mut start = 0;
+        while start <= haystack.len() {
+            let caps = match self.captures_at(haystack, start) {
+                Some(caps) => caps,
+                None => break,
+            };
+            let m = group0(&caps);
+            start = next_start(haystack, &m);
+            if !follows(matches.last().map(group0).as_ref(), &m) {
+                matches.push(caps);
+            }
+        }
+        matches
+    }
+
+    /// The index of the capture group called `name`, if there is one.
+    fn capture_index(&self, _name: &str) -> Option<usize> {
+        None
+    }
+}
+
+/// The span of each capture group in a match, group 0 being the match.
+pub type Captures = Vec<Option<Range<usize>>>;
+
+/// The span of the whole match.
+pub(crate) fn group0(caps: &Captures) -> Range<usize> {
+    caps[0].clone().unwrap_or_default()
+}
+
+/// Where to look for the next match after `m`. An empty match would be
+/// found again and again at the same spot, so step over the next char.
+fn next_start(haystack: &[u8], m: &Range<usize>) -> usize {
+    if m.is_empty() {
+        m.end + utf8::decode(haystack, m.end).1.max(1)
+    } else {
+        m.end
+    }
+}
+
+/// Whether `m` is an empty match right at the end of the `previous` one.
+fn follows(previous: Option<&Range<usize>>, m: &Range<usize>) -> bool {
+    m.is_empty() && previous.is_some_and(|previous| previous.end == m.start)
+}
+
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        (**self).find_at(haystack, start)
+    }
+
+    fn captures_at(&self, haystack: &[u8], start: usize) -> Option<Captures> {
+        (**self).captures_at(haystack, start)
+    }
+
+    fn capture_index(&self, name: &str) -> Option<usize> {
+        (**self).capture_index(name)
+    }
+}
+
+/// Matches a fixed string, optionally ignoring case.
+pub struct LiteralMatcher {
+    needle: Vec<u8>,
+    folded: Option<Vec<char>>,
+}
+
+impl LiteralMatcher {
+    pub fn new(query: &str, ignore_case: bool) -> LiteralMatcher {
+        LiteralMatcher {
+            needle: query.as_bytes().to_vec(),
+            folded: ignore_case.then(|| query.chars().map(utf8::fold).collect()),
+        }
+    }
+
+    /// Compares chars of the haystack one at a time against the folded
+    /// query. Offsets therefore always point into the original haystack,
+    /// even when a char and its lowercase form have different lengths.
+    fn match_folded_at(folded: &[char], haystack: &[u8], start: usize) -> Option<usize> {
+        let mut at = start;
+        for &want in folded {
+            match utf8::decode(haystack, at) {
+                (Some(c), width) if utf8::fold(c) == want => at += width,
+                _ => return None,
+            }
+        }
+        Some(at)
+    }
+}
+
+impl Matcher for LiteralMatcher {
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        if start > haystack.len() {
+            return None;
+        }
+
+        match &self.folded {
+            None => {
+                if self.needle.is_empty() {
+                    return Some(start..start);
+                }
+                haystack[start..]
+                    .windows(self.needle.len())
+                    .position(|window| window == self.needle.as_slice())
+                    .map(|i| start + i..start + i + self.needle.len())
+            }
+            Some(folded) => {
+                let mut at = start;
+                loop {
+                    if let Some(end) = Self::match_folded_at(folded, haystack, at) {
+                        return Some(at..end);
+                    }
+                    match utf8::decode(haystack, at) {
+                        (_, 0) => return None,
+                        (_, width) => at += width,
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Matcher for Regex {
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        Regex::find_at(self, haystack, start)
+    }
+
+    fn captures_at(&self, haystack: &[u8], start: usize) -> Option<Captures> {
+        Regex::captures_at(self, haystack, start)
+    }
+
+    fn capture_index(&self, name: &str) -> Option<usize> {
+        Regex::capture_index(self, name)
+    }
+}
+
+impl Matcher for AhoCorasick {
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        AhoCorasick::find_at(self, haystack, start)
+    }
+}
+
+/// Wraps another matcher so that it only reports whole words: a match
+/// must not have a word character right before or right after it. Word
+/// characters are Unicode letters, digits and `_`. Only the inner matcher's
+/// leftmost match at each position is tried, which makes this exact for a
+/// single fixed string; several fixed strings and regular expressions are
+/// better off with `\b{start-half}` and `\b{end-half}` around them instead.
+pub struct WordMatcher<M> {
+    inner: M,
+}
+
+impl<M: Matcher> WordMatcher<M> {
+    pub fn new(inner: M) -> WordMatcher<M> {
+        WordMatcher { inner }
+    }
+}
+
+impl<M: Matcher> Matcher for WordMatcher<M> {
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        self.captures_at(haystack, start).map(|caps| group0(&caps))
+    }
+
+    fn captures_at(&self, haystack: &[u8], start: usize) -> Option<Captures> {
+        let mut at = start;
+        loop {
+            let caps = self.inner.captures_at(haystack, at)?;
+            let m = group0(&caps);
+            let before = utf8::decode_last(haystack, m.start).0;
+            let after = utf8::decode(haystack, m.end).0;
+            if !before.is_some_and(utf8::is_word_char) && !after.is_some_and(utf8::is_word_char) {
+                return Some(caps);
+            }
+            // `sun` in `sunshine sun` fails at first, so try again one char
+            // further along.
+            match utf8::decode(haystack, m.start) {
+                (_, 0) => return None,
+                (_, width) => at = m.start + width,
+            }
+        }
+    }
+
+    fn capture_index(&self, name: &str) -> Option<usize> {
+        self.inner.capture_index(name)
+    }
+}
+
+/// Wraps another matcher so that it only matches lines which match in
+/// their entirety. The inner matcher's leftmost match has to cover the
+/// whole line, which makes this exact for fixed strings; regular
+/// expressions are better off anchored with `^` and `$` instead.
+pub struct LineMatcher<M> {
+    inner: M,
+}
+
+impl<M: Matcher> LineMatcher<M> {
+    pub fn new(inner: M) -> LineMatcher<M> {
+        LineMatcher { inner }
+    }
+}
+
+impl<M: Matcher> Matcher for LineMatcher<M> {
+    fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        self.captures_at(haystack, start).map(|caps| group0(&caps))
+    }
+
+    fn captures_at(&self, haystack: &[u8], start: usize) -> Option<Captures> {
+        if start > 0 {
+            return None;
+        }
+        self.inner
+            .captures_at(haystack, 0)
+            .filter(|caps| group0(caps) == (0..haystack.len()))
+    }
+
+    fn capture_index(&self, name: &str) -> Option<usize> {
+        self.inner.capture_index(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_finds_every_match() {
+        let matcher = LiteralMatcher::new("ab", false);
+        assert_eq!(vec![0..2, 3..5], matcher.find_all(b"ab ab a"));
+        assert!(!matcher.is_match(b"AB"));
+    }
+
+    #[test]
+    fn literal_ignore_case_reports_original_offsets() {
+        // 'İ' lowercases to two chars, so it must not throw the offsets off.
+        let matcher = LiteralMatcher::new("death", true);
+        let haystack = "İ DEATH".as_bytes();
+        assert_eq!(vec![3..8], matcher.find_all(haystack));
+    }
+
+    #[test]
+    fn whole_words_and_lines() {
+        let word = WordMatcher::new(LiteralMatcher::new("sun", true));
+        assert_eq!(vec![9..12, 18..21], word.find_all(b"sunshine SUN,_sun sun"));
+        assert!(!word.is_match("\u{e9}sun".as_bytes()));
+
+        let line = LineMatcher::new(LiteralMatcher::new("sun", true));
+        assert!(line.is_match(b"Sun"));
+        assert!(!line.is_match(b"sun "));
+    }
+
+    #[test]
+    fn empty_matches_advance() {
+        let matcher = Regex::new("x*").unwrap();
+        assert_eq!(vec![0..0, 1..2], matcher.find_all(b"ax"));
+    }
+}
@@ -0,0 +1,689 @@
+//! Formats search results the way grep does.
+//!
+//! Matching lines are written as `path:line` and context lines as
+//! `path-line`, with a `--` line between groups that are not next to each
+//! other. The path is left out when there is only one input.
+//!
+//! Instead of the lines themselves, a `Printer` can also print just how many
+//! lines matched in each file, or just the names of the files that did or
+//! did not match. Or it can describe everything as JSON; see the `json`
+//! module.
+
+use std::{
+    io::{self, Write},
+    str::FromStr,
+};
+
+use crate::{
+    color::{self, Palette},
+    json::{self, Stats},
+    matcher::{group0, Matcher},
+    replace::Replacement,
+    searcher::{Line, Sink},
+    utf8,
+};
+
+/// What to print for each input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Every matching line, along with any context.
+    #[default]
+    Lines,
+    /// Every match on its own line, without the rest of the line it is in.
+    OnlyMatching,
+    /// The number of matching lines.
+    Count,
+    /// The path, if there is at least one matching line.
+    FilesWithMatches,
+    /// The path, if there are no matching lines.
+    FilesWithoutMatch,
+    /// Nothing at all. The search stops at the first match.
+    Quiet,
+    /// A JSON object for every line, input and the whole run.
+    Json,
+}
+
+/// How columns are counted, as given to `--column-unit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColumnUnit {
+    #[default]
+    Bytes,
+    /// Unicode chars, so that `é` counts once however it is encoded.
+    Chars,
+}
+
+impl FromStr for ColumnUnit {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<ColumnUnit, &'static str> {
+        match s {
+            "bytes" => Ok(ColumnUnit::Bytes),
+            "chars" => Ok(ColumnUnit::Chars),
+            _ => Err("column unit must be either bytes or chars"),
+        }
+    }
+}
+
+/// Options for printing results.
+#[derive(Debug, Clone, Default)]
+pub struct Printer {
+    pub mode: Mode,
+    /// Start each line with its line number.
+    pub line_number: bool,
+    /// Start each line with the column, counting from 1, where the match
+    /// in it starts. Lines without a match get no column.
+    pub column: bool,
+    /// What the column counts.
+    pub column_unit: ColumnUnit,
+    /// Start each line with the offset of its first byte in the input. With
+    /// `Mode::OnlyMatching`, this is the offset of the match instead.
+    pub byte_offset: bool,
+    /// Write `--` between groups of lines that are not adjacent. This only
+    /// makes sense when context lines are printed.
+    pub group_separator: bool,
+    /// Print matching lines with every match replaced by this. Context
+    /// lines are left alone.
+    pub replace: Option<Replacement>,
+    /// Highlight matches, paths and separators with these colors.
+    pub colors: Option<Palette>,
+    /// Flush the output after every line, so that results show up as soon
+    /// as they are found even when the writer is buffered.
+    pub line_buffered: bool,
+}
+
+impl Printer {
+    /// Creates a sink that prints results to `wtr`. Use one sink for a whole
+    /// run so that groups are separated across files too. The `matcher` is
+    /// used to find the text to highlight.
+    pub fn sink<'p, W: Write>(&'p self, matcher: &'p dyn Matcher, wtr: W) -> PrintSink<'p, W> {
+        PrintSink {
+            printer: self,
+            matcher,
+            wtr: Output { wtr, failed: false },
+            path: None,
+            stats: Stats::default(),
+            totals: Stats::default(),
+            wrote_any: false,
+            wrote_file: false,
+        }
+    }
+}
+
+/// A `Sink` which prints every line it is given.
+pub struct PrintSink<'p, W> {
+    printer: &'p Printer,
+    matcher: &'p dyn Matcher,
+    wtr: Output<W>,
+    /// The path as it is on disk, which need not be UTF-8.
+    path: Option<Vec<u8>>,
+    /// What has been found in the current input.
+    stats: Stats,
+    /// What has been found in all inputs so far.
+    totals: Stats,
+    /// Whether anything has been written at all.
+    wrote_any: bool,
+    /// Whether anything has been written for the current path.
+    wrote_file: bool,
+}
+
+impl<W: Write> PrintSink<'_, W> {
+    /// Starts a new input. Lines are prefixed with `path` when it is given.
+    pub fn begin(&mut self, path: Option<&[u8]>) -> io::Result<()> {
+        self.path = path.map(<[u8]>::to_vec);
+        self.stats = Stats::default();
+        self.totals.searches += 1;
+        self.wrote_file = false;
+        if self.printer.mode == Mode::Json {
+            json::begin(&mut self.wtr, path)?;
+        }
+        Ok(())
+    }
+
+    /// Whether writing to the output has failed. The searcher can't tell
+    /// the sink's errors from its reader's, so this is how to find out
+    /// which one went wrong.
+    pub fn write_failed(&self) -> bool {
+        self.wtr.failed
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+
+    /// Whether any line has matched so far, in any input.
+    pub fn has_match(&self) -> bool {
+        self.totals.matched_lines > 0
+    }
+
+    /// Ends the whole run, writing the totals if the mode has them.
+    pub fn summarize(&mut self) -> io::Result<()> {
+        if self.printer.mode == Mode::Json {
+            json::summary(&mut self.wtr, &self.totals)?;
+            self.end_line()?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the current input. This is where the count or the path is
+    /// printed in the modes that only print one line per input.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.stats.matched_lines > 0 {
+            self.totals.searches_with_match += 1;
+        }
+
+        let colors = self.printer.colors.as_ref();
+        let path = self.path.as_deref().unwrap_or_default();
+        match self.printer.mode {
+            Mode::Lines | Mode::OnlyMatching | Mode::Quiet => return Ok(()),
+            Mode::Json => json::end(&mut self.wtr, self.path.as_deref(), &self.stats)?,
+            Mode::Count => {
+                if self.path.is_some() {
+                    paint(&mut self.wtr, colors, |p| &p.path, path)?;
+                    paint(&mut self.wtr, colors, |p| &p.separator, b":")?;
+                }
+                writeln!(self.wtr, "{}", self.stats.matched_lines)?;
+            }
+            Mode::FilesWithMatches | Mode::FilesWithoutMatch => {
+                let matched = self.stats.matched_lines > 0;
+                if matched != (self.printer.mode == Mode::FilesWithMatches) {
+                    return Ok(());
+                }
+                paint(&mut self.wtr, colors, |p| &p.path, path)?;
+                self.wtr.write_all(b"\n")?;
+            }
+        }
+        self.end_line()
+    }
+
+    fn write_line(&mut self, line: &Line<'_>, matched: bool) -> io::Result<bool> {
+        // The first group of a file is separated from the last group of the
+        // previous one, like any other pair of groups.
+        if !self.wrote_file && self.wrote_any && self.printer.group_separator {
+            self.write_group_separator()?;
+        }
+        self.wrote_any = true;
+        self.wrote_file = true;
+
+        let separator: &[u8] = if matched { b":" } else { b"-" };
+        let column = if self.printer.column {
+            self.matcher.find_at(line.bytes, 0).map(|m| m.start)
+        } else {
+            None
+        };
+        self.write_prefix(line, column, line.byte_offset, separator)?;
+
+        let printer = self.printer;
+        let sgr = printer.colors.as_ref().map(|palette| {
+            if matched {
+                &palette.matched
+            } else {
+                &palette.context_matched
+            }
+        });
+        match (&printer.replace, sgr) {
+            (Some(replacement), _) if matched => {
+                self.write_replaced(line.bytes, replacement, sgr)?;
+            }
+            (_, Some(sgr)) => self.write_highlighted(line.bytes, sgr)?,
+            (_, None) => self.wtr.write_all(line.bytes)?,
+        }
+        self.wtr.write_all(b"\n")?;
+        self.end_line()?;
+        Ok(true)
+    }
+
+    /// Writes each match in `line` on a line of its own.
+    fn write_matches(&mut self, line: &Line<'_>) -> io::Result<bool> {
+        let mut replaced = Vec::new();
+        for caps in self.matcher.captures_all(line.bytes) {
+            let span = group0(&caps);
+            if span.is_empty() {
+                continue;
+            }
+            let byte_offset = line.byte_offset + span.start as u64;
+            self.write_prefix(line, Some(span.start), byte_offset, b":")?;
+
+            let text = match &self.printer.replace {
+                Some(replacement) => {
+                    replaced.clear();
+                    replacement.expand(self.matcher, line.bytes, &caps, &mut replaced);
+                    &replaced
+                }
+                None => &line.bytes[span],
+            };
+            match &self.printer.colors {
+                Some(palette) => color::paint(&mut self.wtr, &palette.matched, text)?,
+                None => self.wtr.write_all(text)?,
+            }
+            self.wtr.write_all(b"\n")?;
+            self.end_line()?;
+        }
+        Ok(true)
+    }
+
+    /// Writes the path, line number, column and byte offset that come
+    /// before a line, each followed by `separator`. `start` is where the
+    /// match starts in the line, in bytes, if there is one.
+    fn write_prefix(
+        &mut self,
+        line: &Line<'_>,
+        start: Option<usize>,
+        byte_offset: u64,
+        separator: &[u8],
+    ) -> io::Result<()> {
+        let colors = self.printer.colors.as_ref();
+        if let Some(path) = &self.path {
+            paint(&mut self.wtr, colors, |p| &p.path, path)?;
+            paint(&mut self.wtr, colors, |p| &p.separator, separator)?;
+        }
+        if self.printer.line_number {
+            let number = line.number.to_string();
+            paint(&mut self.wtr, colors, |p| &p.line_number, number.as_bytes())?;
+            paint(&mut self.wtr, colors, |p| &p.separator, separator)?;
+        }
+        if let Some(start) = start.filter(|_| self.printer.column) {
+            let column = match self.printer.column_unit {
+                ColumnUnit::Bytes => start,
+                ColumnUnit::Chars => utf8::char_count(&line.bytes[..start]),
+            };
+            let column = (column + 1).to_string();
+            paint(&mut self.wtr, colors, |p| &p.line_number, column.as_bytes())?;
+            paint(&mut self.wtr, colors, |p| &p.separator, separator)?;
+        }
+        if self.printer.byte_offset {
+            let offset = byte_offset.to_string();
+            paint(&mut self.wtr, colors, |p| &p.byte_offset, offset.as_bytes())?;
+            paint(&mut self.wtr, colors, |p| &p.separator, separator)?;
+        }
+        Ok(())
+    }
+
+    fn write_json(&mut self, line: &Line<'_>, matched: bool) -> io::Result<bool> {
+        let spans: Vec<_> = self
+            .matcher
+            .find_all(line.bytes)
+            .into_iter()
+            .filter(|span| !span.is_empty())
+            .collect();
+        if matched {
+            self.stats.matches += spans.len() as u64;
+            self.totals.matches += spans.len() as u64;
+        }
+        let kind = if matched { "match" } else { "context" };
+        json::line(&mut self.wtr, kind, self.path.as_deref(), line, &spans)?;
+        self.end_line()?;
+        Ok(true)
+    }
+
+    /// Called after every line written, to flush it if line buffered.
+    fn end_line(&mut self) -> io::Result<()> {
+        if self.printer.line_buffered {
+            self.wtr.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes `bytes` with every match wrapped in the color `sgr`. Spans
+    /// come from the matcher, so they are byte offsets into the original
+    /// line even when matching ignored case.
+    fn write_highlighted(&mut self, bytes: &[u8], sgr: &str) -> io::Result<()> {
+        let mut last = 0;
+        for span in self.matcher.find_all(bytes) {
+            if span.is_empty() {
+                continue;
+            }
+            self.wtr.write_all(&bytes[last..span.start])?;
+            color::paint(&mut self.wtr, sgr, &bytes[span.clone()])?;
+            last = span.end;
+        }
+        self.wtr.write_all(&bytes[last..])
+    }
+
+    /// Writes `bytes` with every match swapped for its replacement, which
+    /// is colored with `sgr`, if given.
+    fn write_replaced(
+        &mut self,
+        bytes: &[u8],
+        replacement: &Replacement,
+        sgr: Option<&String>,
+    ) -> io::Result<()> {
+        let mut last = 0;
+        let mut replaced = Vec::new();
+        for caps in self.matcher.captures_all(bytes) {
+            let span = group0(&caps);
+            self.wtr.write_all(&bytes[last..span.start])?;
+            replaced.clear();
+            replacement.expand(self.matcher, bytes, &caps, &mut replaced);
+            match sgr {
+                Some(sgr) => color::paint(&mut self.wtr, sgr, &replaced)?,
+                None => self.wtr.write_all(&replaced)?,
+            }
+            last = span.end;
+        }
+        self.wtr.write_all(&bytes[last..])
+    }
+
+    fn write_group_separator(&mut self) -> io::Result<()> {
+        let colors = self.printer.colors.as_ref();
+        paint(&mut self.wtr, colors, |p| &p.separator, b"--")?;
+        self.wtr.write_all(b"\n")?;
+        self.end_line()
+    }
+}
+
+/// A writer which remembers whether a write to it has ever failed.
+struct Output<W> {
+    wtr: W,
+    failed: bool,
+}
+
+impl<W: Write> Output<W> {
+    fn check<T>(&mut self, result: io::Result<T>) -> io::Result<T> {
+        if let Err(err) = &result {
+            // `write_all` retries interrupted writes, so they don't count.
+            self.failed |= err.kind() != io::ErrorKind::Interrupted;
+        }
+        result
+    }
+}
+
+impl<W: Write> Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.wtr.write(buf);
+        self.check(result)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.wtr.flush();
+        self.check(result)
+    }
+}
+
+/// Writes `bytes` in the color `pick` selects, if colors are enabled.
+fn paint<W: Write>(
+    wtr: &mut W,
+    colors: Option<&Palette>,
+    pick: impl Fn(&Palette) -> &String,
+    bytes: &[u8],
+) -> io::Result<()> {
+    match colors {
+        Some(palette) => color::paint(wtr, pick(palette), bytes),
+        None => wtr.write_all(bytes),
+    }
+}
+
+impl<W: Write> Sink for PrintSink<'_, W> {
+    fn matched(&mut self, line: &Line<'_>) -> io::Result<bool> {
+        self.stats.matched_lines += 1;
+        self.totals.matched_lines += 1;
+        match self.printer.mode {
+            Mode::Lines => self.write_line(line, true),
+            Mode::OnlyMatching => self.write_matches(line),
+            Mode::Json => self.write_json(line, true),
+            Mode::Count => Ok(true),
+            // One match is all it takes to know the answer, so there's no
+            // need to read the rest of the input.
+            Mode::FilesWithMatches | Mode::FilesWithoutMatch | Mode::Quiet => Ok(false),
+        }
+    }
+
+    fn context(&mut self, line: &Line<'_>) -> io::Result<bool> {
+        match self.printer.mode {
+            Mode::Lines => self.write_line(line, false),
+            Mode::Json => self.write_json(line, false),
+            _ => Ok(true),
+        }
+    }
+
+    fn context_break(&mut self) -> io::Result<bool> {
+        if self.printer.mode == Mode::Lines && self.printer.group_separator {
+            self.write_group_separator()?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{matcher::LiteralMatcher, regex::Regex, searcher::Searcher};
+
+    #[test]
+    fn context_groups_across_files() {
+        let matcher = LiteralMatcher::new("sun", false);
+        let searcher = Searcher {
+            after_context: 1,
+            ..Searcher::default()
+        };
+        let printer = Printer {
+            group_separator: true,
+            ..Printer::default()
+        };
+        let mut out = Vec::new();
+        let mut sink = printer.sink(&matcher, &mut out);
+
+        sink.begin(Some(b"a.txt")).unwrap();
+        searcher
+            .search_reader(&matcher, &b"sun\none\ntwo\nsun\n"[..], &mut sink)
+            .unwrap();
+        sink.begin(Some(b"b.txt")).unwrap();
+        searcher
+            .search_reader(&matcher, &b"sun\n"[..], &mut sink)
+            .unwrap();
+
+        assert_eq!(
+            "a.txt:sun\na.txt-one\n--\na.txt:sun\n--\nb.txt:sun\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn counts_and_file_names() {
+        let matcher = LiteralMatcher::new("sun", false);
+        let inputs: [(&str, &[u8]); 3] = [
+            ("a.txt", b"sun\nmoon\nsun\n"),
+            ("b.txt", b"moon\n"),
+            ("c.txt", b"sun\n"),
+        ];
+        let print = |mode| {
+            let printer = Printer {
+                mode,
+                ..Printer::default()
+            };
+            let mut out = Vec::new();
+            let mut sink = printer.sink(&matcher, &mut out);
+            for (path, contents) in inputs {
+                sink.begin(Some(path.as_bytes())).unwrap();
+                Searcher::default()
+                    .search_reader(&matcher, contents, &mut sink)
+                    .unwrap();
+                sink.finish().unwrap();
+            }
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!("a.txt:2\nb.txt:0\nc.txt:1\n", print(Mode::Count));
+        assert_eq!("a.txt\nc.txt\n", print(Mode::FilesWithMatches));
+        assert_eq!("b.txt\n", print(Mode::FilesWithoutMatch));
+        assert_eq!("", print(Mode::Quiet));
+    }
+
+    #[test]
+    fn stops_reading_once_a_file_matches() {
+        let matcher = LiteralMatcher::new("sun", false);
+        let printer = Printer {
+            mode: Mode::FilesWithMatches,
+            ..Printer::default()
+        };
+        let mut out = Vec::new();
+        let mut sink = printer.sink(&matcher, &mut out);
+        let mut input = io::Cursor::new(b"sun\nsun\nsun\n");
+
+        sink.begin(Some(b"a.txt")).unwrap();
+        Searcher::default()
+            .search_reader(&matcher, &mut input, &mut sink)
+            .unwrap();
+
+        assert_eq!(4, input.position());
+    }
+
+    #[test]
+    fn only_matching_with_columns() {
+        let matcher = LiteralMatcher::new("kelvin", true);
+        let printer = Printer {
+            mode: Mode::OnlyMatching,
+            line_number: true,
+            column: true,
+            ..Printer::default()
+        };
+        let mut out = Vec::new();
+        let mut sink = printer.sink(&matcher, &mut out);
+
+        sink.begin(Some(b"f")).unwrap();
+        Searcher::default()
+            .search_reader(
+                &matcher,
+                "none\n\u{212A}ELVIN, kelvin.\n".as_bytes(),
+                &mut sink,
+            )
+            .unwrap();
+
+        assert_eq!(
+            "f:2:1:\u{212A}ELVIN\nf:2:11:kelvin\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn prefixes_with_numbers_columns_and_offsets() {
+        let matcher = LiteralMatcher::new("sun", false);
+        let input = "moon\n\u{e9}t\u{e9} sun\n".as_bytes();
+        let print = |column_unit| {
+            let printer = Printer {
+                line_number: true,
+                column: true,
+                column_unit,
+                byte_offset: true,
+                ..Printer::default()
+            };
+            let mut out = Vec::new();
+            let mut sink = printer.sink(&matcher, &mut out);
+            sink.begin(None).unwrap();
+            Searcher::default()
+                .search_reader(&matcher, input, &mut sink)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!("2:7:5:\u{e9}t\u{e9} sun\n", print(ColumnUnit::Bytes));
+        assert_eq!("2:5:5:\u{e9}t\u{e9} sun\n", print(ColumnUnit::Chars));
+    }
+
+    #[test]
+    fn json_events() {
+        let matcher = LiteralMatcher::new("sun", false);
+        let searcher = Searcher {
+            before_context: 1,
+            ..Searcher::default()
+        };
+        let printer = Printer {
+            mode: Mode::Json,
+            ..Printer::default()
+        };
+        let mut out = Vec::new();
+        let mut sink = printer.sink(&matcher, &mut out);
+
+        sink.begin(Some(b"a.txt")).unwrap();
+        searcher
+            .search_reader(&matcher, &b"moon\nsun \xff sun\n"[..], &mut sink)
+            .unwrap();
+        sink.finish().unwrap();
+        // Paths don't have to be UTF-8 either.
+        sink.begin(Some(b"b\xff")).unwrap();
+        sink.finish().unwrap();
+        sink.summarize().unwrap();
+
+        let expected = [
+            r#"{"type":"begin","data":{"path":{"text":"a.txt"}}}"#,
+            r#"{"type":"context","data":{"path":{"text":"a.txt"},"line_number":1,"absolute_offset":0,"line":{"text":"moon"},"submatches":[]}}"#,
+            r#"{"type":"match","data":{"path":{"text":"a.txt"},"line_number":2,"absolute_offset":5,"line":{"bytes":"c3VuIP8gc3Vu"},"submatches":[{"match":{"text":"sun"},"start":0,"end":3},{"match":{"text":"sun"},"start":6,"end":9}]}}"#,
+            r#"{"type":"end","data":{"path":{"text":"a.txt"},"stats":{"matched_lines":1,"matches":2}}}"#,
+            r#"{"type":"begin","data":{"path":{"bytes":"Yv8="}}}"#,
+            r#"{"type":"end","data":{"path":{"bytes":"Yv8="},"stats":{"matched_lines":0,"matches":0}}}"#,
+            r#"{"type":"summary","data":{"stats":{"searches":2,"searches_with_match":1,"matched_lines":1,"matches":2}}}"#,
+        ];
+        assert_eq!(expected.join("\n") + "\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn replaces_matches() {
+        let matcher = Regex::new(r"(\w+)@(?P<host>\w+)").unwrap();
+        let print = |mode| {
+            let printer = Printer {
+                mode,
+                replace: Some(Replacement::new("${host}:$1", true)),
+                ..Printer::default()
+            };
+            let mut out = Vec::new();
+            let mut sink = printer.sink(&matcher, &mut out);
+            sink.begin(None).unwrap();
+            Searcher::default()
+                .search_reader(&matcher, &b"to: ann@home, bob@work\n"[..], &mut sink)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!("to: home:ann, work:bob\n", print(Mode::Lines));
+        assert_eq!("home:ann\nwork:bob\n", print(Mode::OnlyMatching));
+    }
+
+    #[test]
+    fn remembers_write_errors() {
+        struct Closed;
+        impl Write for Closed {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let matcher = LiteralMatcher::new("sun", false);
+        let printer = Printer::default();
+        let mut sink = printer.sink(&matcher, Closed);
+
+        sink.begin(None).unwrap();
+        let err = Searcher::default()
+            .search_reader(&matcher, &b"moon\nsun\n"[..], &mut sink)
+            .unwrap_err();
+
+        assert_eq!(io::ErrorKind::BrokenPipe, err.kind());
+        assert!(sink.write_failed());
+    }
+
+    #[test]
+    fn highlights_case_insensitive_matches() {
+        // The Kelvin sign is three bytes but lowercases to a one byte 'k',
+        // so highlighting the lowercased line would put the colors in the
+        // wrong place.
+        let matcher = LiteralMatcher::new("kelvin", true);
+        let printer = Printer {
+            colors: Some(Palette::parse("ms=1:fn=35:se=")),
+            ..Printer::default()
+        };
+        let mut out = Vec::new();
+        let mut sink = printer.sink(&matcher, &mut out);
+
+        sink.begin(Some(b"f")).unwrap();
+        Searcher::default()
+            .search_reader(&matcher, "at \u{212A}ELVIN.\n".as_bytes(), &mut sink)
+            .unwrap();
+
+        assert_eq!(
+            "\x1b[35mf\x1b[0m:at \x1b[1m\u{212A}ELVIN\x1b[0m.\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Compiles a syntax tree into a program for the Pike VM.
+
+use super::parse::{Assertion, Ast, Class};
+use super::Error;
+
+/// Upper bound on the number of instructions in a program. Counted
+/// repetitions are expanded, so `(a{1000}){1000}` would otherwise produce a
+/// program with a million instructions.
+const MAX_INSTS: usize = 100_000;
+
+#[derive(Debug, Clone)]
+pub(crate) enum Inst {
+    Char { c: char, fold: bool },
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    Save(usize),
+    Split(usize, usize),
+    Jmp(usize),
+    Match,
+}
+
+#[derive(Debug)]
+pub(crate) struct Program {
+    pub(crate) insts: Vec<Inst>,
+    pub(crate) slots: usize,
+}
+
+pub(crate) fn compile(ast: &Ast, groups: usize) -> Result<Program, Error> {
+    let mut compiler = Compiler { insts: Vec::new() };
+
+    compiler.push(Inst::Save(0))?;
+    compiler.compile(ast)?;
+    compiler.push(Inst::Save(1))?;
+    compiler.push(Inst::Match)?;
+
+    Ok(Program {
+        insts: compiler.insts,
+        slots: groups * 2,
+    })
+}
+
+struct Compiler {
+    insts: Vec<Inst>,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize, Error> {
+        if self.insts.len() >= MAX_INSTS {
+            return Err(Error::new("compiled pattern is too large", 0));
+        }
+        self.insts.push(inst);
+        Ok(self.insts.len() - 1)
+    }
+
+    fn next(&self) -> usize {
+        self.insts.len()
+    }
+
+    /// Points a previously pushed `Split` or `Jmp` at `target`.
+    fn patch(&mut self, at: usize, target: usize, second: bool) {
+        match &mut self.insts[at] {
+            Inst::Split(_, b) if second => *b = target,
+            Inst::Split(a, _) => *a = target,
+            Inst::Jmp(t) => *t = target,
+            _ => unreachable!("only splits and jumps are patched"),
+        }
+    }
+
+    fn compile(&mut self, ast: &Ast) -> Result<(), Error> {
+        match ast {
+            Ast::Empty => {}
+            Ast::Literal { c, fold } => {
+                self.push(Inst::Char { c: *c, fold: *fold })?;
+            }
+            Ast::Any => {
+                self.push(Inst::Any)?;
+            }
+            Ast::Class(class) => {
+                self.push(Inst::Class(class.clone()))?;
+            }
+            Ast::Assertion(assertion) => {
+                self.push(Inst::Assert(*assertion))?;
+            }
+            Ast::Capture { index, ast } => {
+                self.push(Inst::Save(index * 2))?;
+                self.compile(ast)?;
+                self.push(Inst::Save(index * 2 + 1))?;
+            }
+            Ast::Concat(items) => {
+                for item in items {
+                    self.compile(item)?;
+                }
+            }
+            Ast::Alternate(branches) => self.compile_alternate(branches)?,
+            Ast::Repeat {
+                ast,
+                min,
+                max,
+                greedy,
+            } => self.compile_repeat(ast, *min, *max, *greedy)?,
+        }
+
+        Ok(())
+    }
+
+    fn compile_alternate(&mut self, branches: &[Ast]) -> Result<(), Error> {
+        let mut jumps = Vec::new();
+        for (i, branch) in branches.iter().enumerate() {
+            if i + 1 == branches.len() {
+                self.compile(branch)?;
+                break;
+            }
+
+            let split = self.push(Inst::Split(0, 0))?;
+            self.patch(split, split + 1, false);
+            self.compile(branch)?;
+            jumps.push(self.push(Inst::Jmp(0))?);
+            let next = self.next();
+            self.patch(split, next, true);
+        }
+
+        let end = self.next();
+        for jump in jumps {
+            self.patch(jump, end, false);
+        }
+
+        Ok(())
+    }
+
+    fn compile_repeat(
+        &mut self,
+        ast: &Ast,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    ) -> Result<(), Error> {
+        for _ in 0..min {
+            self.compile(ast)?;
+        }
+
+        match max {
+            None => {
+                // L1: split L2, L3
+                // L2: <ast>
+                //     jmp L1
+                // L3:
+                let split = self.push(Inst::Split(0, 0))?;
+                self.compile(ast)?;
+                self.push(Inst::Jmp(split))?;
+                let end = self.next();
+                self.patch_split(split, split + 1, end, greedy);
+            }
+            Some(max) => {
+                // Each optional copy may bail out to the very end.
+                let mut splits = Vec::new();
+                for _ in min..max {
+                    splits.push(self.push(Inst::Split(0, 0))?);
+                    self.compile(ast)?;
+                }
+                let end = self.next();
+                for split in splits {
+                    self.patch_split(split, split + 1, end, greedy);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Orders the two branches of a repetition split so that the preferred
+    /// one comes first.
+    fn patch_split(&mut self, split: usize, body: usize, end: usize, greedy: bool) {
+        let (first, second) = if greedy { (body, end) } else { (end, body) };
+        self.patch(split, first, false);
+        self.patch(split, second, true);
+    }
+}
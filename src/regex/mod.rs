@@ -0,0 +1,241 @@
+//! A small regular expression engine.
+//!
+//! Patterns are parsed into a syntax tree, compiled into a program of simple
+//! instructions and run on a Pike VM. The VM never backtracks, so matching
+//! takes time linear in the size of the input for every pattern, including
+//! the classic "evil" ones like `(a*)*b`.
+//!
+//! Supported syntax: literals, `.`, character classes (`[a-z]`, `[^0-9]`,
+//! `\d`, `\w`, `\s` and their negations), alternation, the repetition
+//! operators `*`, `+`, `?` and `{n,m}` (each with a lazy `?` form), the
+//! anchors `^`, `$`, `\b` and `\B`, the half boundaries `\b{start-half}`
+//! and `\b{end-half}`, which only check that there is no word character on
+//! one side, capture groups (`(...)`, `(?P<name>...)`
+//! or `(?<name>...)`), non-capturing groups (`(?:...)`) and the `i` flag.
+
+mod compile;
+mod parse;
+mod pikevm;
+
+use std::{error::Error as StdError, fmt, ops::Range};
+
+use compile::Program;
+
+/// A compiled regular expression.
+#[derive(Debug)]
+pub struct Regex {
+    prog: Program,
+    names: Vec<Option<String>>,
+}
+
+impl Regex {
+    /// Compiles `pattern` with the default options.
+    pub fn new(pattern: &str) -> Result<Regex, Error> {
+        RegexBuilder::new(pattern).build()
+    }
+
+    /// The number of capture groups, counting the implicit group 0 for the
+    /// whole match.
+    pub fn captures_len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// The index of the capture group called `name`, if there is one.
+    pub fn capture_index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n.as_deref() == Some(name))
+    }
+
+    /// Whether the pattern matches anywhere in `haystack`.
+    pub fn is_match(&self, haystack: &[u8]) -> bool {
+        self.find_at(haystack, 0).is_some()
+    }
+
+    /// Finds the leftmost-first match starting at or after `start`.
+    ///
+    /// Anchors and word boundaries still look at the whole haystack, so `^`
+    /// never matches at `start` unless `start` is zero.
+    pub fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        let mut slots = [None; 2];
+        if pikevm::exec(&self.prog, haystack, start, &mut slots) {
+            Some(slots[0]?..slots[1]?)
+        } else {
+            None
+        }
+    }
+
+    /// Like `find_at`, but also reports the span of every capture group.
+    /// Groups which did not take part in the match are `None`.
+    pub fn captures_at(&self, haystack: &[u8], start: usize) -> Option<Vec<Option<Range<usize>>>> {
+        let mut slots = vec![None; self.prog.slots];
+        if !pikevm::exec(&self.prog, haystack, start, &mut slots) {
+            return None;
+        }
+
+        let groups = slots
+            .chunks(2)
+            .map(|pair| match pair {
+                [Some(start), Some(end)] => Some(*start..*end),
+                _ => None,
+            })
+            .collect();
+        Some(groups)
+    }
+}
+
+/// Configures and compiles a `Regex`.
+pub struct RegexBuilder<'p> {
+    pattern: &'p str,
+    case_insensitive: bool,
+}
+
+impl<'p> RegexBuilder<'p> {
+    pub fn new(pattern: &'p str) -> RegexBuilder<'p> {
+        RegexBuilder {
+            pattern,
+            case_insensitive: false,
+        }
+    }
+
+    /// Starts the pattern with the `i` flag set. An inline `(?-i)` can still
+    /// turn it off again.
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut RegexBuilder<'p> {
+        self.case_insensitive = yes;
+        self
+    }
+
+    pub fn build(&self) -> Result<Regex, Error> {
+        let parsed = parse::parse(self.pattern, self.case_insensitive)?;
+        let prog = compile::compile(&parsed.ast, parsed.names.len())?;
+
+        Ok(Regex {
+            prog,
+            names: parsed.names,
+        })
+    }
+}
+
+/// An error found while parsing or compiling a pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    msg: String,
+    position: usize,
+}
+
+impl Error {
+    fn new(msg: &str, position: usize) -> Error {
+        Error {
+            msg: msg.to_string(),
+            position,
+        }
+    }
+
+    /// The offset, in chars, into the pattern where the error was found.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "regex parse error at position {}: {}",
+            self.position, self.msg
+        )
+    }
+}
+
+impl StdError for Error {}
+
+/// Escapes every character in `literal` which has a special meaning, so
+/// that the result matches `literal` exactly.
+pub fn escape(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'h>(pattern: &str, haystack: &'h str) -> Option<&'h str> {
+        let re = Regex::new(pattern).unwrap();
+        re.find_at(haystack.as_bytes(), 0).map(|m| &haystack[m])
+    }
+
+    #[test]
+    fn classes_alternation_and_repetition() {
+        assert_eq!(
+            Some("2023-10-05"),
+            find(r"\d{4}-\d\d-\d{2}", "on 2023-10-05 at")
+        );
+        assert_eq!(Some("cat"), find("dog|cat", "a cat and a dog"));
+        assert_eq!(Some("abbb"), find("ab+", "xabbbc"));
+        assert_eq!(Some("ab"), find("ab+?", "xabbbc"));
+        assert_eq!(Some("x_1"), find(r"[^\s]\w+", "  x_1 "));
+        assert_eq!(None, find("[a-c]{3}", "abxabx"));
+    }
+
+    #[test]
+    fn anchors_and_boundaries() {
+        assert_eq!(Some("sun"), find("^sun", "sun shines"));
+        assert_eq!(None, find("^shines", "sun shines"));
+        assert_eq!(Some("shines"), find("shines$", "sun shines"));
+        assert_eq!(None, find(r"\bsun\b", "sunshine"));
+        assert_eq!(Some("sun"), find(r"\bsun\b", "the sun."));
+        assert_eq!(
+            Some("-sun"),
+            find(r"\b{start-half}-sun\b{end-half}", "a -sun")
+        );
+        assert_eq!(None, find(r"\b{start-half}-sun", "a-sun"));
+        assert_eq!(None, find(r"sun\b{end-half}", "sunny"));
+    }
+
+    #[test]
+    fn capture_groups() {
+        let re = Regex::new(r"(?P<key>\w+)=(\w+)?").unwrap();
+        let caps = re.captures_at(b"a key=value", 0).unwrap();
+        assert_eq!(vec![Some(2..11), Some(2..5), Some(6..11)], caps);
+        assert_eq!(Some(1), re.capture_index("key"));
+
+        let caps = re.captures_at(b"key=", 0).unwrap();
+        assert_eq!(vec![Some(0..4), Some(0..3), None], caps);
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let re = RegexBuilder::new("colou?r")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        assert_eq!(Some(12..18), re.find_at("a different CoLoUr".as_bytes(), 0));
+        assert_eq!(Some("ΣΑ"), find("(?i)σα", "ΣΑ"));
+        assert_eq!(Some("Ab"), find("(?i)a(?-i)b", "AB Ab"));
+    }
+
+    #[test]
+    fn pathological_patterns_finish() {
+        let haystack = "a".repeat(5000);
+        assert_eq!(None, find("(a*)*b", &haystack));
+        assert_eq!(None, find("(a|aa)+$b", &haystack));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Regex::new("(abc").is_err());
+        assert!(Regex::new("abc)").is_err());
+        assert!(Regex::new("[a-").is_err());
+        assert!(Regex::new("*a").is_err());
+        assert!(Regex::new("a{3,2}").is_err());
+        assert!(Regex::new(r"\q").is_err());
+        assert!(Regex::new(r"\b{start}").is_err());
+        assert!(Regex::new(r"\b{start-half").is_err());
+        assert_eq!(1, Regex::new("a)").unwrap_err().position());
+    }
+}
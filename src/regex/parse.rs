@@ -0,0 +1,583 @@
+//! Turns a pattern string into an abstract syntax tree.
+//!
+//! The grammar is the familiar subset of Perl-style regular expressions:
+//!
+//! ```text
+//! alternation := concat ('|' concat)*
+//! concat      := repeat*
+//! repeat      := atom ('*' | '+' | '?' | '{n}' | '{n,}' | '{n,m}')* '?'?
+//! atom        := literal | '.' | '^' | '$' | class | escape | group
+//! group       := '(' alternation ')' | '(?:' ... ')' | '(?P<name>' ... ')'
+//!              | '(?<name>' ... ')' | '(?flags)' | '(?flags:' ... ')'
+//! ```
+//!
+//! Backreferences and lookaround are deliberately not supported, since they
+//! cannot be matched in linear time.
+
+use super::Error;
+use crate::utf8;
+
+/// The largest count accepted in a `{n,m}` repetition.
+const MAX_REPEAT: u32 = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Ast {
+    Empty,
+    Literal {
+        c: char,
+        fold: bool,
+    },
+    Any,
+    Class(Class),
+    Assertion(Assertion),
+    Capture {
+        index: usize,
+        ast: Box<Ast>,
+    },
+    Repeat {
+        ast: Box<Ast>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+    Concat(Vec<Ast>),
+    Alternate(Vec<Ast>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Assertion {
+    StartLine,
+    EndLine,
+    WordBoundary,
+    NotWordBoundary,
+    /// `\b{start-half}`: not right after a word character.
+    WordStartHalf,
+    /// `\b{end-half}`: not right before a word character.
+    WordEndHalf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Perl {
+    Digit,
+    Word,
+    Space,
+}
+
+impl Perl {
+    fn matches(self, c: char) -> bool {
+        match self {
+            Perl::Digit => c.is_ascii_digit(),
+            Perl::Word => utf8::is_word_char(c),
+            Perl::Space => c.is_whitespace(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ClassItem {
+    Range(char, char),
+    Perl { kind: Perl, negated: bool },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Class {
+    pub(crate) items: Vec<ClassItem>,
+    pub(crate) negated: bool,
+    pub(crate) fold: bool,
+}
+
+impl Class {
+    fn perl(kind: Perl, negated: bool) -> Class {
+        Class {
+            items: vec![ClassItem::Perl { kind, negated }],
+            negated: false,
+            fold: false,
+        }
+    }
+
+    fn contains_exact(&self, c: char) -> bool {
+        self.items.iter().any(|item| match *item {
+            ClassItem::Range(lo, hi) => lo <= c && c <= hi,
+            ClassItem::Perl { kind, negated } => kind.matches(c) != negated,
+        })
+    }
+
+    pub(crate) fn matches(&self, c: char) -> bool {
+        let mut found = self.contains_exact(c);
+        if !found && self.fold {
+            let mut upper = c.to_uppercase();
+            let upper = match (upper.next(), upper.next()) {
+                (Some(u), None) => u,
+                _ => c,
+            };
+            found = self.contains_exact(utf8::fold(c)) || self.contains_exact(upper);
+        }
+        found != self.negated
+    }
+}
+
+/// The result of parsing: the tree plus the names of every capture group.
+/// Group 0 is the whole match and never has a name.
+pub(crate) struct Parsed {
+    pub(crate) ast: Ast,
+    pub(crate) names: Vec<Option<String>>,
+}
+
+pub(crate) fn parse(pattern: &str, fold: bool) -> Result<Parsed, Error> {
+    let mut parser = Parser {
+        chars: pattern.chars().collect(),
+        pos: 0,
+        fold,
+        names: vec![None],
+    };
+
+    let ast = parser.parse_alternation()?;
+    if parser.pos < parser.chars.len() {
+        // The only way to stop early at the top level is an unopened `)`.
+        return Err(parser.error("unmatched closing parenthesis"));
+    }
+
+    Ok(Parsed {
+        ast,
+        names: parser.names,
+    })
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    fold: bool,
+    names: Vec<Option<String>>,
+}
+
+impl Parser {
+    fn error(&self, msg: &str) -> Error {
+        Error::new(msg, self.pos)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_alternation(&mut self) -> Result<Ast, Error> {
+        // Inline flags like `(?i)` last until the end of the enclosing group.
+        let fold = self.fold;
+        let mut branches = vec![self.parse_concat()?];
+        while self.eat('|') {
+            branches.push(self.parse_concat()?);
+        }
+        self.fold = fold;
+
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Ast::Alternate(branches)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Ast, Error> {
+        let mut items = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            if let Some(atom) = self.parse_atom()? {
+                let repeated = self.parse_repeat(atom)?;
+                items.push(repeated);
+            }
+        }
+
+        Ok(match items.len() {
+            0 => Ast::Empty,
+            1 => items.pop().unwrap(),
+            _ => Ast::Concat(items),
+        })
+    }
+
+    fn parse_repeat(&mut self, mut ast: Ast) -> Result<Ast, Error> {
+        loop {
+            let start = self.pos;
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => match self.parse_counts()? {
+                    Some(counts) => counts,
+                    None => return Ok(ast),
+                },
+                _ => return Ok(ast),
+            };
+            if start == self.pos {
+                self.pos += 1;
+            }
+
+            if matches!(ast, Ast::Empty | Ast::Assertion(_)) {
+                self.pos = start;
+                return Err(self.error("repetition operator missing expression"));
+            }
+
+            let greedy = !self.eat('?');
+            ast = Ast::Repeat {
+                ast: Box::new(ast),
+                min,
+                max,
+                greedy,
+            };
+        }
+    }
+
+    /// Parses `{n}`, `{n,}` or `{n,m}`. A `{` that does not start a valid
+    /// counted repetition is treated as a literal, like most engines do.
+    fn parse_counts(&mut self) -> Result<Option<(u32, Option<u32>)>, Error> {
+        let start = self.pos;
+        self.pos += 1;
+
+        let min = match self.parse_number()? {
+            Some(n) => n,
+            None => {
+                self.pos = start;
+                return Ok(None);
+            }
+        };
+        let max = if self.eat(',') {
+            self.parse_number()?
+        } else {
+            Some(min)
+        };
+        if !self.eat('}') {
+            self.pos = start;
+            return Ok(None);
+        }
+
+        if let Some(max) = max {
+            if max < min {
+                self.pos = start;
+                return Err(self.error("invalid repetition range, minimum exceeds maximum"));
+            }
+        }
+
+        Ok(Some((min, max)))
+    }
+
+    fn parse_number(&mut self) -> Result<Option<u32>, Error> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Ok(None);
+        }
+
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        match digits.parse::<u32>() {
+            Ok(n) if n <= MAX_REPEAT => Ok(Some(n)),
+            _ => {
+                self.pos = start;
+                Err(self.error("repetition count is too large"))
+            }
+        }
+    }
+
+    /// Parses a single atom. Returns `None` for constructs which only change
+    /// parser state, such as a bare `(?i)` flag group.
+    fn parse_atom(&mut self) -> Result<Option<Ast>, Error> {
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        let ast = match c {
+            '.' => Ast::Any,
+            '^' => Ast::Assertion(Assertion::StartLine),
+            '$' => Ast::Assertion(Assertion::EndLine),
+            '[' => Ast::Class(self.parse_class()?),
+            '(' => return self.parse_group(),
+            '\\' => self.parse_escape()?,
+            '*' | '+' | '?' => {
+                self.pos -= 1;
+                return Err(self.error("repetition operator missing expression"));
+            }
+            c => Ast::Literal { c, fold: self.fold },
+        };
+
+        Ok(Some(ast))
+    }
+
+    fn parse_group(&mut self) -> Result<Option<Ast>, Error> {
+        let open = self.pos - 1;
+
+        let index = if self.eat('?') {
+            let named = if self.eat('P') {
+                if !self.eat('<') {
+                    return Err(self.error("expected '<' after '(?P'"));
+                }
+                true
+            } else {
+                self.eat('<')
+            };
+            if named {
+                let name = self.parse_group_name()?;
+                self.names.push(Some(name));
+                Some(self.names.len() - 1)
+            } else {
+                // A flag group: either `(?flags)` or `(?flags:...)`.
+                let fold = self.parse_flags()?;
+                if self.eat(')') {
+                    self.fold = fold;
+                    return Ok(None);
+                }
+                if !self.eat(':') {
+                    return Err(self.error("expected ':' or ')' after group flags"));
+                }
+
+                let outer = std::mem::replace(&mut self.fold, fold);
+                let ast = self.parse_alternation()?;
+                self.fold = outer;
+                self.close_group(open)?;
+                return Ok(Some(ast));
+            }
+        } else {
+            self.names.push(None);
+            Some(self.names.len() - 1)
+        };
+
+        let ast = self.parse_alternation()?;
+        self.close_group(open)?;
+
+        Ok(Some(match index {
+            Some(index) => Ast::Capture {
+                index,
+                ast: Box::new(ast),
+            },
+            None => ast,
+        }))
+    }
+
+    fn close_group(&mut self, open: usize) -> Result<(), Error> {
+        if self.eat(')') {
+            Ok(())
+        } else {
+            Err(Error::new("unclosed group", open))
+        }
+    }
+
+    fn parse_group_name(&mut self) -> Result<String, Error> {
+        let start = self.pos;
+        let mut name = String::new();
+        while let Some(c) = self.bump() {
+            if c == '>' {
+                if name.is_empty() {
+                    self.pos = start;
+                    return Err(self.error("empty capture group name"));
+                }
+                if self.names.iter().flatten().any(|n| *n == name) {
+                    self.pos = start;
+                    return Err(self.error("duplicate capture group name"));
+                }
+                return Ok(name);
+            }
+            if !(utf8::is_word_char(c)) {
+                self.pos -= 1;
+                return Err(self.error("invalid character in capture group name"));
+            }
+            name.push(c);
+        }
+
+        self.pos = start;
+        Err(self.error("unclosed capture group name"))
+    }
+
+    /// Parses flags such as `i` or `-i` and returns the resulting fold state.
+    fn parse_flags(&mut self) -> Result<bool, Error> {
+        let mut fold = self.fold;
+        let mut negate = false;
+        while let Some(c) = self.peek() {
+            match c {
+                'i' => fold = !negate,
+                '-' if !negate => negate = true,
+                ':' | ')' => break,
+                _ => return Err(self.error("unrecognized flag")),
+            }
+            self.pos += 1;
+        }
+
+        Ok(fold)
+    }
+
+    fn parse_escape(&mut self) -> Result<Ast, Error> {
+        let c = match self.bump() {
+            Some(c) => c,
+            None => {
+                self.pos -= 1;
+                return Err(self.error("incomplete escape sequence"));
+            }
+        };
+
+        Ok(match c {
+            'b' if self.peek() == Some('{') => self.parse_word_boundary()?,
+            'b' => Ast::Assertion(Assertion::WordBoundary),
+            'B' => Ast::Assertion(Assertion::NotWordBoundary),
+            'A' => Ast::Assertion(Assertion::StartLine),
+            'z' => Ast::Assertion(Assertion::EndLine),
+            _ => match self.parse_escaped_char(c)? {
+                Escaped::Char(c) => Ast::Literal { c, fold: self.fold },
+                Escaped::Perl(kind, negated) => Ast::Class(Class::perl(kind, negated)),
+            },
+        })
+    }
+
+    /// Parses the `{...}` after `\b`: `{start-half}` or `{end-half}`.
+    fn parse_word_boundary(&mut self) -> Result<Ast, Error> {
+        let rest = &self.chars[self.pos + 1..];
+        let name: String = match rest.iter().position(|&c| c == '}') {
+            Some(len) => rest[..len].iter().collect(),
+            None => return Err(self.error("unrecognized word boundary")),
+        };
+        let assertion = match name.as_str() {
+            "start-half" => Assertion::WordStartHalf,
+            "end-half" => Assertion::WordEndHalf,
+            _ => return Err(self.error("unrecognized word boundary")),
+        };
+        self.pos += name.chars().count() + 2;
+        Ok(Ast::Assertion(assertion))
+    }
+
+    /// Handles escapes that are valid both inside and outside a class.
+    fn parse_escaped_char(&mut self, c: char) -> Result<Escaped, Error> {
+        Ok(match c {
+            'd' => Escaped::Perl(Perl::Digit, false),
+            'D' => Escaped::Perl(Perl::Digit, true),
+            'w' => Escaped::Perl(Perl::Word, false),
+            'W' => Escaped::Perl(Perl::Word, true),
+            's' => Escaped::Perl(Perl::Space, false),
+            'S' => Escaped::Perl(Perl::Space, true),
+            'n' => Escaped::Char('\n'),
+            't' => Escaped::Char('\t'),
+            'r' => Escaped::Char('\r'),
+            'x' => Escaped::Char(self.parse_hex()?),
+            c if c.is_ascii_alphanumeric() => {
+                self.pos -= 2;
+                return Err(self.error("unrecognized escape sequence"));
+            }
+            c => Escaped::Char(c),
+        })
+    }
+
+    /// Parses the digits of `\xNN` or `\x{N...}`.
+    fn parse_hex(&mut self) -> Result<char, Error> {
+        let start = self.pos;
+        let braced = self.eat('{');
+        let mut digits = String::new();
+        while let Some(c) = self.peek() {
+            if braced && c == '}' {
+                self.pos += 1;
+                break;
+            }
+            if !c.is_ascii_hexdigit() || (!braced && digits.len() == 2) {
+                break;
+            }
+            digits.push(c);
+            self.pos += 1;
+        }
+
+        let valid = if braced {
+            !digits.is_empty() && self.chars[self.pos - 1] == '}'
+        } else {
+            digits.len() == 2
+        };
+        let c = u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .filter(|_| valid);
+
+        c.ok_or_else(|| {
+            self.pos = start;
+            self.error("invalid hexadecimal escape")
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<Class, Error> {
+        let open = self.pos - 1;
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+
+        // A `]` right after the opening bracket is a literal.
+        let mut first = true;
+        loop {
+            let c = match self.bump() {
+                Some(c) => c,
+                None => return Err(Error::new("unclosed character class", open)),
+            };
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let lo = match self.parse_class_char(c)? {
+                Escaped::Char(lo) => lo,
+                Escaped::Perl(kind, negated) => {
+                    items.push(ClassItem::Perl { kind, negated });
+                    continue;
+                }
+            };
+
+            // `-` is a range only between two chars, otherwise a literal.
+            if self.peek() == Some('-') && !matches!(self.chars.get(self.pos + 1), Some(']') | None)
+            {
+                self.pos += 1;
+                let next = self.bump().unwrap();
+                let hi = match self.parse_class_char(next)? {
+                    Escaped::Char(hi) => hi,
+                    Escaped::Perl(..) => {
+                        return Err(self.error("invalid range in character class"));
+                    }
+                };
+                if hi < lo {
+                    return Err(self.error("invalid range in character class"));
+                }
+                items.push(ClassItem::Range(lo, hi));
+            } else {
+                items.push(ClassItem::Range(lo, lo));
+            }
+        }
+
+        Ok(Class {
+            items,
+            negated,
+            fold: self.fold,
+        })
+    }
+
+    fn parse_class_char(&mut self, c: char) -> Result<Escaped, Error> {
+        if c != '\\' {
+            return Ok(Escaped::Char(c));
+        }
+        match self.bump() {
+            Some(c) => self.parse_escaped_char(c),
+            None => {
+                self.pos -= 1;
+                Err(self.error("incomplete escape sequence"))
+            }
+        }
+    }
+}
+
+enum Escaped {
+    Char(char),
+    Perl(Perl, bool),
+}
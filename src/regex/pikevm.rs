@@ -0,0 +1,196 @@
+//! A Pike VM: simulates every thread of the compiled program in lock step,
+//! so each byte of the haystack is looked at once per instruction at most.
+//! That gives `O(haystack * pattern)` time no matter what the pattern is,
+//! which is the guarantee a backtracking engine cannot make.
+
+use super::compile::{Inst, Program};
+use super::parse::Assertion;
+use crate::utf8;
+
+/// Runs `prog` over `haystack`, looking for the leftmost-first match that
+/// starts at or after `start`. On success `slots` holds the capture offsets;
+/// only as many groups as fit in `slots` are tracked.
+pub(crate) fn exec(
+    prog: &Program,
+    haystack: &[u8],
+    start: usize,
+    slots: &mut [Option<usize>],
+) -> bool {
+    let nslots = slots.len().min(prog.slots);
+    let mut clist = Threads::new(prog.insts.len(), nslots);
+    let mut nlist = Threads::new(prog.insts.len(), nslots);
+    let mut stack = Vec::new();
+    let mut caps = vec![None; nslots];
+    let mut matched = false;
+
+    let mut at = start;
+    loop {
+        // Seed a new thread at this position, with the lowest priority,
+        // until some thread has found a match.
+        if !matched {
+            caps.iter_mut().for_each(|slot| *slot = None);
+            add_thread(prog, &mut clist, &mut stack, 0, haystack, at, &mut caps);
+        }
+        if matched && clist.is_empty() {
+            break;
+        }
+
+        let (c, width) = utf8::decode(haystack, at);
+        for i in 0..clist.dense.len() {
+            let pc = clist.dense[i];
+            let step = match &prog.insts[pc] {
+                Inst::Match => {
+                    slots[..nslots].copy_from_slice(clist.caps(pc));
+                    matched = true;
+                    // Every remaining thread has a lower priority.
+                    break;
+                }
+                Inst::Char { c: want, fold } => {
+                    c.is_some_and(|c| c == *want || (*fold && utf8::fold(c) == utf8::fold(*want)))
+                }
+                Inst::Any => c.is_some_and(|c| c != '\n'),
+                Inst::Class(class) => c.is_some_and(|c| class.matches(c)),
+                _ => false,
+            };
+            if step {
+                caps.copy_from_slice(clist.caps(pc));
+                add_thread(
+                    prog,
+                    &mut nlist,
+                    &mut stack,
+                    pc + 1,
+                    haystack,
+                    at + width,
+                    &mut caps,
+                );
+            }
+        }
+
+        if at >= haystack.len() {
+            break;
+        }
+        std::mem::swap(&mut clist, &mut nlist);
+        nlist.clear();
+        at += width;
+    }
+
+    matched
+}
+
+enum Frame {
+    Explore(usize),
+    Restore(usize, Option<usize>),
+}
+
+/// Follows every empty transition from `pc`, adding the instructions which
+/// consume input to `list`. An explicit stack keeps deeply nested patterns
+/// from overflowing the real one.
+fn add_thread(
+    prog: &Program,
+    list: &mut Threads,
+    stack: &mut Vec<Frame>,
+    pc: usize,
+    haystack: &[u8],
+    at: usize,
+    caps: &mut [Option<usize>],
+) {
+    stack.push(Frame::Explore(pc));
+    while let Some(frame) = stack.pop() {
+        let pc = match frame {
+            Frame::Explore(pc) => pc,
+            Frame::Restore(slot, old) => {
+                caps[slot] = old;
+                continue;
+            }
+        };
+        if !list.insert(pc) {
+            continue;
+        }
+
+        match &prog.insts[pc] {
+            Inst::Jmp(target) => stack.push(Frame::Explore(*target)),
+            Inst::Split(first, second) => {
+                stack.push(Frame::Explore(*second));
+                stack.push(Frame::Explore(*first));
+            }
+            Inst::Save(slot) if *slot < caps.len() => {
+                stack.push(Frame::Restore(*slot, caps[*slot]));
+                caps[*slot] = Some(at);
+                stack.push(Frame::Explore(pc + 1));
+            }
+            Inst::Save(_) => stack.push(Frame::Explore(pc + 1)),
+            Inst::Assert(assertion) => {
+                if holds(*assertion, haystack, at) {
+                    stack.push(Frame::Explore(pc + 1));
+                }
+            }
+            Inst::Char { .. } | Inst::Any | Inst::Class(_) | Inst::Match => {
+                list.caps_mut(pc).copy_from_slice(caps);
+            }
+        }
+    }
+}
+
+fn holds(assertion: Assertion, haystack: &[u8], at: usize) -> bool {
+    match assertion {
+        Assertion::StartLine => at == 0,
+        Assertion::EndLine => at == haystack.len(),
+        Assertion::WordBoundary | Assertion::NotWordBoundary => {
+            let before = utf8::decode_last(haystack, at)
+                .0
+                .is_some_and(utf8::is_word_char);
+            let after = utf8::decode(haystack, at).0.is_some_and(utf8::is_word_char);
+            (before != after) == (assertion == Assertion::WordBoundary)
+        }
+        Assertion::WordStartHalf => !utf8::decode_last(haystack, at)
+            .0
+            .is_some_and(utf8::is_word_char),
+        Assertion::WordEndHalf => !utf8::decode(haystack, at).0.is_some_and(utf8::is_word_char),
+    }
+}
+
+/// A sparse set of program counters, kept in priority order, with the
+/// capture slots of each thread stored alongside.
+struct Threads {
+    dense: Vec<usize>,
+    sparse: Vec<usize>,
+    caps: Vec<Option<usize>>,
+    nslots: usize,
+}
+
+impl Threads {
+    fn new(len: usize, nslots: usize) -> Threads {
+        Threads {
+            dense: Vec::with_capacity(len),
+            sparse: vec![0; len],
+            caps: vec![None; len * nslots],
+            nslots,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    fn insert(&mut self, pc: usize) -> bool {
+        let i = self.sparse[pc];
+        if i < self.dense.len() && self.dense[i] == pc {
+            return false;
+        }
+        self.sparse[pc] = self.dense.len();
+        self.dense.push(pc);
+        true
+    }
+
+    fn clear(&mut self) {
+        self.dense.clear();
+    }
+
+    fn caps(&self, pc: usize) -> &[Option<usize>] {
+        &self.caps[pc * self.nslots..(pc + 1) * self.nslots]
+    }
+
+    fn caps_mut(&mut self, pc: usize) -> &mut [Option<usize>] {
+        &mut self.caps[pc * self.nslots..(pc + 1) * self.nslots]
+    }
+}
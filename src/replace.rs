@@ -0,0 +1,143 @@
+//! Replacement text for `--replace`.
+//!
+//! In regex mode, the replacement can refer to capture groups: `$1` or
+//! `${1}` by number, `${name}` by name, and `$$` for a literal `$`. A
+//! reference to a group that doesn't exist, or that didn't take part in the
+//! match, is replaced by nothing. With fixed strings there are no groups,
+//! so the replacement is always used as it is.
+
+use crate::matcher::{Captures, Matcher};
+
+/// A parsed replacement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replacement {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Index(usize),
+    Name(String),
+}
+
+impl Replacement {
+    /// Parses `text`, looking for group references only if `interpolate`
+    /// is set.
+    pub fn new(text: &str, interpolate: bool) -> Replacement {
+        if !interpolate {
+            return Replacement {
+                parts: vec![Part::Literal(text.to_string())],
+            };
+        }
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = text;
+        while let Some(i) = rest.find('$') {
+            literal.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+
+            let (part, len) = if rest.starts_with('$') {
+                (None, 1)
+            } else if let Some(braced) = rest.strip_prefix('{') {
+                match braced.find('}') {
+                    Some(end) => (Some(group(&braced[..end])), end + 2),
+                    None => (None, 0),
+                }
+            } else {
+                let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+                match digits {
+                    0 => (None, 0),
+                    _ => (Some(group(&rest[..digits])), digits),
+                }
+            };
+
+            match part {
+                Some(part) => {
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                }
+                // `$$`, or a `$` which isn't a reference at all.
+                None => literal.push('$'),
+            }
+            rest = &rest[len..];
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Replacement { parts }
+    }
+
+    /// Appends the replacement for the match `caps` of `matcher` in
+    /// `haystack` to `dst`.
+    pub fn expand<M: Matcher + ?Sized>(
+        &self,
+        matcher: &M,
+        haystack: &[u8],
+        caps: &Captures,
+        dst: &mut Vec<u8>,
+    ) {
+        for part in &self.parts {
+            let index = match part {
+                Part::Literal(literal) => {
+                    dst.extend_from_slice(literal.as_bytes());
+                    continue;
+                }
+                Part::Index(index) => Some(*index),
+                Part::Name(name) => matcher.capture_index(name),
+            };
+            if let Some(Some(span)) = index.and_then(|index| caps.get(index)) {
+                dst.extend_from_slice(&haystack[span.clone()]);
+            }
+        }
+    }
+}
+
+fn group(name: &str) -> Part {
+    match name.parse() {
+        Ok(index) => Part::Index(index),
+        Err(_) => Part::Name(name.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{matcher::LiteralMatcher, regex::Regex};
+
+    fn replace<M: Matcher>(matcher: &M, replacement: &Replacement, haystack: &str) -> String {
+        let mut out = Vec::new();
+        for caps in matcher.captures_all(haystack.as_bytes()) {
+            replacement.expand(matcher, haystack.as_bytes(), &caps, &mut out);
+            out.push(b'|');
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn expands_groups() {
+        let regex = Regex::new(r"(?P<key>\w+)=(\w+)?").unwrap();
+        let replacement = Replacement::new("${2}:$key:${key}$$1 $9$", true);
+
+        assert_eq!(
+            "b:$key:a$1 $|:$key:c$1 $|",
+            replace(&regex, &replacement, "a=b c=")
+        );
+    }
+
+    #[test]
+    fn literal_replacements_are_not_interpolated() {
+        let matcher = LiteralMatcher::new("sun", true);
+        let replacement = Replacement::new("$1 moon", false);
+
+        assert_eq!(
+            "$1 moon|$1 moon|",
+            replace(&matcher, &replacement, "Sun sun")
+        );
+    }
+}
@@ -0,0 +1,440 @@
+//! Rewrites files with their matches replaced, for `--in-place`.
+//!
+//! A file is never written to directly. The new contents go to a temporary
+//! file next to it, which is then renamed over the original, so that the
+//! file is either entirely old or entirely new even if minigrep is killed
+//! halfway through. The temporary file gets the original's permissions
+//! before it takes its place.
+//!
+//! Each replacement can also be confirmed one by one before it is made; see
+//! `replace_lines_with`.
+
+use std::{
+    fs::{self, OpenOptions, Permissions},
+    io::{self, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
+
+use crate::{
+    matcher::{group0, Matcher},
+    replace::Replacement,
+    searcher::trim_terminator,
+};
+
+/// How many unchanged lines to show around each change in a diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// Replaces every match in every line of `contents`, returning the new
+/// contents and how many replacements were made. Lines are matched
+/// without their terminators, just like when searching.
+pub fn replace_lines<M: Matcher + ?Sized>(
+    matcher: &M,
+    replacement: &Replacement,
+    contents: &[u8],
+) -> (Vec<u8>, u64) {
+    let always = |_: &Edit<'_>| Ok(Answer::Yes);
+    match replace_lines_with(matcher, replacement, contents, always) {
+        Ok(replaced) => replaced,
+        Err(_) => unreachable!("accepting every edit can't fail"),
+    }
+}
+
+/// A replacement waiting to be confirmed.
+#[derive(Debug)]
+pub struct Edit<'a> {
+    /// Every line of the file, with its terminator.
+    pub lines: &'a [&'a [u8]],
+    /// Which of `lines` the match is in.
+    pub index: usize,
+    /// Where the match is in that line.
+    pub span: Range<usize>,
+    /// What the match would be replaced with.
+    pub replacement: &'a [u8],
+}
+
+/// What to do with an `Edit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    Yes,
+    No,
+    /// Make this replacement and all the rest without asking.
+    All,
+    /// Don't make this replacement or any of the rest.
+    Quit,
+}
+
+impl FromStr for Answer {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Answer, &'static str> {
+        match s.to_lowercase().as_str() {
+            "y" | "yes" => Ok(Answer::Yes),
+            "n" | "no" => Ok(Answer::No),
+            "a" | "all" => Ok(Answer::All),
+            "q" | "quit" => Ok(Answer::Quit),
+            _ => Err("please answer y, n, a or q"),
+        }
+    }
+}
+
+/// Like `replace_lines`, but only makes the replacements `confirm` says
+/// yes to. Once it answers `All` or `Quit`, it isn't asked again.
+pub fn replace_lines_with<M, F>(
+    matcher: &M,
+    replacement: &Replacement,
+    contents: &[u8],
+    mut confirm: F,
+) -> io::Result<(Vec<u8>, u64)>
+where
+    M: Matcher + ?Sized,
+    F: FnMut(&Edit<'_>) -> io::Result<Answer>,
+{
+    let lines: Vec<&[u8]> = lines(contents).collect();
+    let mut replaced = Vec::with_capacity(contents.len());
+    let mut count = 0;
+    let mut decided = None;
+    let mut expanded = Vec::new();
+
+    for (index, &raw) in lines.iter().enumerate() {
+        let line = trim_terminator(raw);
+        let mut last = 0;
+        for caps in matcher.captures_all(line) {
+            let span = group0(&caps);
+            expanded.clear();
+            replacement.expand(matcher, line, &caps, &mut expanded);
+
+            let answer = match decided {
+                Some(answer) => answer,
+                None => {
+                    let edit = Edit {
+                        lines: &lines,
+                        index,
+                        span: span.clone(),
+                        replacement: &expanded,
+                    };
+                    confirm(&edit)?
+                }
+            };
+            if matches!(answer, Answer::All | Answer::Quit) {
+                decided = Some(answer);
+            }
+            if matches!(answer, Answer::Yes | Answer::All) {
+                replaced.extend_from_slice(&line[last..span.start]);
+                replaced.extend_from_slice(&expanded);
+                last = span.end;
+                count += 1;
+            }
+        }
+        replaced.extend_from_slice(&raw[last..]);
+    }
+
+    Ok((replaced, count))
+}
+
+/// Shows `edit` to the user: the line it is in with `context` lines on
+/// either side, and the line as it would be after the replacement.
+pub fn write_edit<W: Write>(
+    wtr: &mut W,
+    path: &str,
+    edit: &Edit<'_>,
+    context: usize,
+) -> io::Result<()> {
+    let start = edit.index.saturating_sub(context);
+    let end = (edit.index + context + 1).min(edit.lines.len());
+    let width = end.to_string().len();
+
+    writeln!(wtr, "{path}:{}:", edit.index + 1)?;
+    for index in start..end {
+        let line = trim_terminator(edit.lines[index]);
+        let number = index + 1;
+        if index != edit.index {
+            write!(wtr, "{number:>width$}  ")?;
+            wtr.write_all(line)?;
+            writeln!(wtr)?;
+            continue;
+        }
+
+        write!(wtr, "{number:>width$} -")?;
+        wtr.write_all(&line[..edit.span.start])?;
+        wtr.write_all(b"[-")?;
+        wtr.write_all(&line[edit.span.clone()])?;
+        wtr.write_all(b"-]")?;
+        wtr.write_all(&line[edit.span.end..])?;
+        writeln!(wtr)?;
+
+        write!(wtr, "{number:>width$} +")?;
+        wtr.write_all(&line[..edit.span.start])?;
+        wtr.write_all(b"{+")?;
+        wtr.write_all(edit.replacement)?;
+        wtr.write_all(b"+}")?;
+        wtr.write_all(&line[edit.span.end..])?;
+        writeln!(wtr)?;
+    }
+    Ok(())
+}
+
+/// Replaces the file at `path` with `contents`, keeping a copy of the old
+/// contents at `path.bak` if `backup` is set. When `path` is a symbolic
+/// link, the file it points to is replaced, and the link is left alone.
+pub fn write_atomically(path: &Path, contents: &[u8], backup: bool) -> io::Result<()> {
+    let path = &fs::canonicalize(path)?;
+    let permissions = fs::metadata(path)?.permissions();
+    if backup {
+        fs::copy(path, backup_path(path))?;
+    }
+
+    let temp = temp_path(path);
+    let result = write_new(&temp, contents, permissions).and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        // Don't leave half-written files lying around.
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Writes `contents` to a file which must not exist yet, and makes sure it
+/// has reached the disk before returning. The file gets `permissions`
+/// before anything is written to it, so that private contents are never
+/// readable by others, not even for a moment.
+fn write_new(path: &Path, contents: &[u8], permissions: Permissions) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.set_permissions(permissions)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// `dir/name.ext` becomes `dir/name.ext.bak`.
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// A hidden file in the same directory, since renaming only replaces a
+/// file atomically within one file system.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".minigrep-{}.tmp", process::id()));
+    path.with_file_name(name)
+}
+
+/// Writes a unified diff turning `old` into `new` to `wtr`. The two must
+/// have the same number of lines, as `replace_lines` leaves them, unless
+/// a replacement itself contains newlines.
+pub fn write_diff<W: Write>(wtr: &mut W, path: &str, old: &[u8], new: &[u8]) -> io::Result<()> {
+    let old: Vec<&[u8]> = lines(old).collect();
+    let new: Vec<&[u8]> = lines(new).collect();
+
+    // Lines are paired up one to one, so that a replacement which adds a
+    // line shows up as a change at the end.
+    let len = old.len().max(new.len());
+    let changed: Vec<usize> = (0..len).filter(|&i| old.get(i) != new.get(i)).collect();
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(wtr, "--- a/{path}")?;
+    writeln!(wtr, "+++ b/{path}")?;
+
+    let mut hunk_start = 0;
+    for (i, &line) in changed.iter().enumerate() {
+        let next = changed.get(i + 1);
+        // Changes whose context would overlap go in the same hunk.
+        if next.is_some_and(|&next| next - line <= 2 * DIFF_CONTEXT + 1) {
+            continue;
+        }
+
+        let start = changed[hunk_start].saturating_sub(DIFF_CONTEXT);
+        let end = (line + DIFF_CONTEXT + 1).min(len);
+        let old_end = end.min(old.len());
+        let new_end = end.min(new.len());
+        writeln!(
+            wtr,
+            "@@ -{} +{} @@",
+            range(start, old_end.saturating_sub(start)),
+            range(start, new_end.saturating_sub(start))
+        )?;
+
+        let mut at = start;
+        while at < end {
+            if old.get(at) == new.get(at) {
+                write_diff_line(wtr, b' ', old[at])?;
+                at += 1;
+                continue;
+            }
+            // A run of changed lines is shown as all the old lines, then
+            // all the new ones.
+            let run_end = (at..end).find(|&i| old.get(i) == new.get(i)).unwrap_or(end);
+            for line in old.get(at..run_end.min(old.len())).unwrap_or_default() {
+                write_diff_line(wtr, b'-', line)?;
+            }
+            for line in new.get(at..run_end.min(new.len())).unwrap_or_default() {
+                write_diff_line(wtr, b'+', line)?;
+            }
+            at = run_end;
+        }
+
+        hunk_start = i + 1;
+    }
+
+    Ok(())
+}
+
+/// Formats the start and length of a hunk, which counts lines from 1.
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{len}", start + 1),
+    }
+}
+
+fn write_diff_line<W: Write>(wtr: &mut W, sign: u8, line: &[u8]) -> io::Result<()> {
+    wtr.write_all(&[sign])?;
+    wtr.write_all(line)?;
+    if !line.ends_with(b"\n") {
+        wtr.write_all(b"\n\\ No newline at end of file\n")?;
+    }
+    Ok(())
+}
+
+/// Splits `contents` into lines, each with its terminator.
+fn lines(contents: &[u8]) -> impl Iterator<Item = &[u8]> {
+    contents.split_inclusive(|&b| b == b'\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{matcher::LiteralMatcher, test_util::scratch_dir};
+
+    #[test]
+    fn replaces_in_every_line() {
+        let matcher = LiteralMatcher::new("sun", true);
+        let replacement = Replacement::new("moon", false);
+
+        let (new, count) = replace_lines(&matcher, &replacement, b"Sun\r\nno\nsun sun");
+
+        assert_eq!(3, count);
+        assert_eq!(b"moon\r\nno\nmoon moon", new.as_slice());
+    }
+
+    #[test]
+    fn replaces_only_confirmed_matches() {
+        let matcher = LiteralMatcher::new("sun", false);
+        let replacement = Replacement::new("moon", false);
+        let mut answers = [Answer::No, Answer::Yes, Answer::Quit].into_iter();
+        let mut asked = Vec::new();
+
+        let (new, count) =
+            replace_lines_with(&matcher, &replacement, b"sun sun\nsun\nsun\n", |edit| {
+                asked.push((edit.index, edit.span.start));
+                Ok(answers.next().unwrap())
+            })
+            .unwrap();
+
+        assert_eq!(vec![(0, 0), (0, 4), (1, 0)], asked);
+        assert_eq!(1, count);
+        assert_eq!(b"sun moon\nsun\nsun\n", new.as_slice());
+    }
+
+    #[test]
+    fn shows_edits_in_context() {
+        let lines: [&[u8]; 3] = [b"one\n", b"the sun\n", b"three\n"];
+        let edit = Edit {
+            lines: &lines,
+            index: 1,
+            span: 4..7,
+            replacement: b"moon",
+        };
+        let mut out = Vec::new();
+
+        write_edit(&mut out, "f.txt", &edit, 1).unwrap();
+
+        assert_eq!(
+            "f.txt:2:\n1  one\n2 -the [-sun-]\n2 +the {+moon+}\n3  three\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn diffs_changed_lines() {
+        let old = b"1\n2\n3\nsun\n5\n6\n7\n8\n9\n10\n11\nsun";
+        let new = b"1\n2\n3\nmoon\n5\n6\n7\n8\n9\n10\n11\nmoon";
+        let mut out = Vec::new();
+
+        write_diff(&mut out, "f.txt", old, new).unwrap();
+
+        assert_eq!(
+            "\
+--- a/f.txt
++++ b/f.txt
+@@ -1,7 +1,7 @@
+ 1
+ 2
+ 3
+-sun
++moon
+ 5
+ 6
+ 7
+@@ -9,4 +9,4 @@
+ 9
+ 10
+ 11
+-sun
+\\ No newline at end of file
++moon
+\\ No newline at end of file
+",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn rewrites_files_atomically() {
+        let dir = scratch_dir("rewrite", &[("poem.txt", "the sun\n")]);
+        let path = dir.join("poem.txt");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, Permissions::from_mode(0o600)).unwrap();
+        }
+
+        write_atomically(&path, b"the moon\n", true).unwrap();
+
+        assert_eq!("the moon\n", fs::read_to_string(&path).unwrap());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+        assert_eq!(
+            "the sun\n",
+            fs::read_to_string(dir.join("poem.txt.bak")).unwrap()
+        );
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rewrites_the_target_of_a_symlink() {
+        let dir = scratch_dir("symlink", &[("poem.txt", "the sun\n")]);
+        let path = dir.join("poem.txt");
+        let link = dir.join("link.txt");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        write_atomically(&link, b"the moon\n", false).unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!("the moon\n", fs::read_to_string(&path).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
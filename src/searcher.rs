@@ -0,0 +1,317 @@
+//! Searches any `BufRead` one line at a time.
+//!
+//! Only the current line is kept in memory, so the size of the input does
+//! not matter, and lines are handed over as raw bytes, so input that is not
+//! valid UTF-8 is searched rather than rejected. Each matching line is
+//! passed to a `Sink`, which decides what to do with it.
+//!
+//! Context lines before a match are the one exception: up to
+//! `before_context` of the most recent lines are buffered.
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead},
+};
+
+use crate::matcher::Matcher;
+
+/// A line handed to a `Sink`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line<'b> {
+    /// The line number, starting from 1.
+    pub number: u64,
+    /// The offset of the first byte of the line in the input.
+    pub byte_offset: u64,
+    /// The contents of the line, without its line terminator.
+    pub bytes: &'b [u8],
+}
+
+/// Receives the results of a search.
+pub trait Sink {
+    /// Called with every matching line. Returning `Ok(false)` stops the
+    /// search early, and an error stops it and is passed back to the caller.
+    fn matched(&mut self, line: &Line<'_>) -> io::Result<bool>;
+
+    /// Called with every line printed as context around a match.
+    fn context(&mut self, _line: &Line<'_>) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    /// Called between two groups of matching and context lines that are
+    /// not next to each other. Only called when context is enabled.
+    fn context_break(&mut self) -> io::Result<bool> {
+        Ok(true)
+    }
+}
+
+impl<F: FnMut(&Line<'_>) -> io::Result<bool>> Sink for F {
+    fn matched(&mut self, line: &Line<'_>) -> io::Result<bool> {
+        self(line)
+    }
+}
+
+/// Options for searching a reader.
+#[derive(Debug, Clone, Default)]
+pub struct Searcher {
+    /// Give up on input which looks binary, that is, whose first buffer
+    /// full of bytes contains a NUL byte.
+    pub skip_binary: bool,
+    /// Report the lines that do *not* match instead of those that do.
+    pub invert_match: bool,
+    /// How many lines to report before each matching line.
+    pub before_context: usize,
+    /// How many lines to report after each matching line.
+    pub after_context: usize,
+}
+
+impl Searcher {
+    pub fn search_reader<M, R, S>(&self, matcher: &M, mut reader: R, sink: &mut S) -> io::Result<()>
+    where
+        M: Matcher + ?Sized,
+        R: BufRead,
+        S: Sink + ?Sized,
+    {
+        if self.skip_binary && reader.fill_buf()?.contains(&0) {
+            return Ok(());
+        }
+
+        let mut buf = Vec::new();
+        let mut number = 0;
+        let mut byte_offset = 0;
+        let mut before = VecDeque::with_capacity(self.before_context);
+        let mut after_left = 0;
+        let mut last_reported = None;
+        loop {
+            buf.clear();
+            let read = reader.read_until(b'\n', &mut buf)?;
+            if read == 0 {
+                return Ok(());
+            }
+            number += 1;
+
+            let line = Line {
+                number,
+                byte_offset,
+                bytes: trim_terminator(&buf),
+            };
+            byte_offset += read as u64;
+
+            let selected = matcher.is_match(line.bytes) != self.invert_match;
+            let keep_going = if selected {
+                after_left = self.after_context;
+                self.report_before(&mut before, sink, &mut last_reported)?
+                    && self.report(&line, true, sink, &mut last_reported)?
+            } else if after_left > 0 {
+                after_left -= 1;
+                self.report(&line, false, sink, &mut last_reported)?
+            } else {
+                if self.before_context > 0 {
+                    // Reuse the oldest buffer once the window is full.
+                    let mut old = if before.len() == self.before_context {
+                        before.pop_front().unwrap()
+                    } else {
+                        Buffered::default()
+                    };
+                    old.number = line.number;
+                    old.byte_offset = line.byte_offset;
+                    old.bytes.clear();
+                    old.bytes.extend_from_slice(line.bytes);
+                    before.push_back(old);
+                }
+                true
+            };
+            if !keep_going {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Reports the buffered lines leading up to a match as context.
+    fn report_before<S: Sink + ?Sized>(
+        &self,
+        before: &mut VecDeque<Buffered>,
+        sink: &mut S,
+        last_reported: &mut Option<u64>,
+    ) -> io::Result<bool> {
+        while let Some(buffered) = before.pop_front() {
+            let line = Line {
+                number: buffered.number,
+                byte_offset: buffered.byte_offset,
+                bytes: &buffered.bytes,
+            };
+            if !self.report(&line, false, sink, last_reported)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Hands a line to the sink, first telling it about any gap since the
+    /// last line it was given, so that context groups can be separated.
+    fn report<S: Sink + ?Sized>(
+        &self,
+        line: &Line<'_>,
+        matched: bool,
+        sink: &mut S,
+        last_reported: &mut Option<u64>,
+    ) -> io::Result<bool> {
+        let has_context = self.before_context > 0 || self.after_context > 0;
+        let gap = last_reported.is_some_and(|last| line.number > last + 1);
+        if has_context && gap && !sink.context_break()? {
+            return Ok(false);
+        }
+        *last_reported = Some(line.number);
+
+        if matched {
+            sink.matched(line)
+        } else {
+            sink.context(line)
+        }
+    }
+}
+
+/// A line kept around in case it turns out to be context for a match.
+#[derive(Default)]
+struct Buffered {
+    number: u64,
+    byte_offset: u64,
+    bytes: Vec<u8>,
+}
+
+/// Searches `reader` with the default `Searcher`, passing every matching
+/// line to `sink`.
+pub fn search_reader<M, R, S>(matcher: &M, reader: R, sink: &mut S) -> io::Result<()>
+where
+    M: Matcher + ?Sized,
+    R: BufRead,
+    S: Sink + ?Sized,
+{
+    Searcher::default().search_reader(matcher, reader, sink)
+}
+
+/// Strips a trailing `\n` or `\r\n`.
+pub(crate) fn trim_terminator(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::LiteralMatcher;
+
+    #[test]
+    fn reports_numbers_and_offsets() {
+        let matcher = LiteralMatcher::new("sun", false);
+        let input: &[u8] = b"the sun\r\nno\nsun \xff\xfe bytes\n";
+        let mut lines = Vec::new();
+
+        search_reader(&matcher, input, &mut |line: &Line<'_>| {
+            lines.push((line.number, line.byte_offset, line.bytes.to_vec()));
+            Ok(true)
+        })
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                (1, 0, b"the sun".to_vec()),
+                (3, 12, b"sun \xff\xfe bytes".to_vec())
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn sink_can_stop_the_search() {
+        let matcher = LiteralMatcher::new("a", false);
+        let mut count = 0;
+
+        search_reader(&matcher, &b"a\na\na\n"[..], &mut |_: &Line<'_>| {
+            count += 1;
+            Ok(false)
+        })
+        .unwrap();
+
+        assert_eq!(1, count);
+    }
+
+    #[test]
+    fn invert_match() {
+        let matcher = LiteralMatcher::new("SUN", true);
+        let searcher = Searcher {
+            invert_match: true,
+            ..Searcher::default()
+        };
+        let mut numbers = Vec::new();
+
+        searcher
+            .search_reader(
+                &matcher,
+                &b"sun
+moon
+Sun
+stars
+"[..],
+                &mut |line: &Line<'_>| {
+                    numbers.push(line.number);
+                    Ok(true)
+                },
+            )
+            .unwrap();
+
+        assert_eq!(vec![2, 4], numbers);
+    }
+
+    #[test]
+    fn context_windows_merge() {
+        let matcher = LiteralMatcher::new("x", false);
+        let searcher = Searcher {
+            before_context: 1,
+            after_context: 1,
+            ..Searcher::default()
+        };
+        let mut events = Vec::new();
+
+        struct Events<'e>(&'e mut Vec<String>);
+        impl Sink for Events<'_> {
+            fn matched(&mut self, line: &Line<'_>) -> io::Result<bool> {
+                self.0.push(format!("{}:", line.number));
+                Ok(true)
+            }
+            fn context(&mut self, line: &Line<'_>) -> io::Result<bool> {
+                self.0.push(format!("{}-", line.number));
+                Ok(true)
+            }
+            fn context_break(&mut self) -> io::Result<bool> {
+                self.0.push(String::from("--"));
+                Ok(true)
+            }
+        }
+
+        let input = b"a\nx\nb\nx\nc\nd\ne\nx\n";
+        searcher
+            .search_reader(&matcher, &input[..], &mut Events(&mut events))
+            .unwrap();
+
+        assert_eq!(vec!["1-", "2:", "3-", "4:", "5-", "--", "7-", "8:"], events);
+    }
+
+    #[test]
+    fn skips_binary_input() {
+        let matcher = LiteralMatcher::new("a", false);
+        let searcher = Searcher {
+            skip_binary: true,
+            ..Searcher::default()
+        };
+
+        searcher
+            .search_reader(
+                &matcher,
+                &b"a\0\na\n"[..],
+                &mut |_: &Line<'_>| -> io::Result<bool> {
+                    panic!("binary input should not be searched")
+                },
+            )
+            .unwrap();
+    }
+}
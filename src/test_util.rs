@@ -0,0 +1,17 @@
+//! Helpers shared by the tests of several modules.
+
+use std::{env, fs, path::PathBuf, process};
+
+/// Creates a fresh directory under the system temp dir for one test,
+/// holding `files`. Their parent directories are created as needed.
+pub(crate) fn scratch_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = env::temp_dir().join(format!("minigrep-{}-{name}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, contents) in files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    dir
+}
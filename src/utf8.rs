@@ -0,0 +1,76 @@
+//! Small helpers for walking UTF-8 encoded bytes one `char` at a time.
+//!
+//! Matchers work on raw bytes so that they can search input which is not
+//! valid UTF-8. Each invalid byte decodes to `None` with a length of one,
+//! which means it never matches a literal or a class, but the search can
+//! still step over it.
+
+/// Decodes the `char` starting at `at`, returning it along with its length
+/// in bytes. Returns `(None, 0)` at the end of `bytes`.
+pub(crate) fn decode(bytes: &[u8], at: usize) -> (Option<char>, usize) {
+    let rest = match bytes.get(at..) {
+        Some(rest) if !rest.is_empty() => rest,
+        _ => return (None, 0),
+    };
+
+    let width = match rest[0] {
+        0x00..=0x7F => 1,
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return (None, 1),
+    };
+
+    match rest.get(..width).map(std::str::from_utf8) {
+        Some(Ok(s)) => (s.chars().next(), width),
+        _ => (None, 1),
+    }
+}
+
+/// Decodes the `char` that ends right before `at`.
+pub(crate) fn decode_last(bytes: &[u8], at: usize) -> (Option<char>, usize) {
+    if at == 0 {
+        return (None, 0);
+    }
+
+    // A char is at most four bytes long, so look back that far for a
+    // leading byte which decodes to exactly the bytes in between.
+    let lowest = at.saturating_sub(4);
+    for start in (lowest..at).rev() {
+        if bytes[start] & 0xC0 != 0x80 {
+            return match decode(bytes, start) {
+                (Some(c), width) if start + width == at => (Some(c), width),
+                _ => (None, 1),
+            };
+        }
+    }
+
+    (None, 1)
+}
+
+/// Counts the chars in `bytes`, counting each invalid byte as one.
+pub(crate) fn char_count(bytes: &[u8]) -> usize {
+    let mut count = 0;
+    let mut at = 0;
+    while at < bytes.len() {
+        at += decode(bytes, at).1;
+        count += 1;
+    }
+    count
+}
+
+/// Folds `c` to a canonical case so that two chars which only differ in
+/// case compare equal. Chars whose lowercase form is longer than one char
+/// are left alone.
+pub(crate) fn fold(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+/// Whether `c` counts as a word character for `\w` and `\b`.
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
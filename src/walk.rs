@@ -0,0 +1,387 @@
+//! Recursively lists the files below a directory.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::ignore::{Gitignore, Verdict};
+
+/// Options for walking a directory tree. The defaults skip hidden files,
+/// honor ignore files, do not follow symbolic links and have no depth limit.
+#[derive(Debug, Clone)]
+pub struct Walker {
+    /// How many directories deep to descend. Files directly inside the
+    /// root are at depth 1.
+    pub max_depth: Option<usize>,
+    /// Include files and directories whose name starts with a `.`.
+    pub hidden: bool,
+    /// Follow symbolic links instead of skipping them.
+    pub follow_links: bool,
+    /// Skip paths matched by `.gitignore`, `.ignore` and `.git/info/exclude`
+    /// files, both those found along the way and those in the directories
+    /// above the root, up to the top of its git repository. This also skips
+    /// `.git` directories.
+    pub ignore_files: bool,
+}
+
+impl Default for Walker {
+    fn default() -> Walker {
+        Walker {
+            max_depth: None,
+            hidden: false,
+            follow_links: false,
+            ignore_files: true,
+        }
+    }
+}
+
+impl Walker {
+    /// Returns an iterator over every regular file below `root`, in sorted
+    /// order. A directory that cannot be read, or a symbolic link that
+    /// points back into one of its own parents, produces an error and is
+    /// skipped; the walk itself carries on.
+    pub fn walk(&self, root: &Path) -> Walk {
+        Walk {
+            walker: self.clone(),
+            root: Some(root.to_path_buf()),
+            stack: Vec::new(),
+            parents: Vec::new(),
+        }
+    }
+}
+
+pub struct Walk {
+    walker: Walker,
+    root: Option<PathBuf>,
+    stack: Vec<Level>,
+    /// Ignore files from outside the walk, highest precedence first.
+    parents: Vec<Parent>,
+}
+
+/// A directory that is being listed.
+struct Level {
+    dir: PathBuf,
+    entries: std::vec::IntoIter<PathBuf>,
+    /// The canonical path of the directory, used to spot symlink loops.
+    canonical: PathBuf,
+    depth: usize,
+    /// Ignore files found in this directory, lowest precedence first.
+    ignores: Vec<Gitignore>,
+}
+
+/// Ignore files from a directory above the root.
+struct Parent {
+    /// Where the root is, relative to the directory the files are in.
+    prefix: PathBuf,
+    ignores: Vec<Gitignore>,
+}
+
+impl Walk {
+    fn push_dir(&mut self, dir: &Path, depth: usize) -> io::Result<()> {
+        let canonical = fs::canonicalize(dir)?;
+        if self.stack.iter().any(|level| level.canonical == canonical) {
+            return Err(io::Error::other(format!(
+                "{}: symbolic link loop detected",
+                dir.display()
+            )));
+        }
+
+        let mut entries = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort();
+
+        let ignores = if self.walker.ignore_files {
+            Self::load_ignores(dir)
+        } else {
+            Vec::new()
+        };
+
+        self.stack.push(Level {
+            dir: dir.to_path_buf(),
+            entries: entries.into_iter(),
+            canonical,
+            depth,
+            ignores,
+        });
+        Ok(())
+    }
+
+    /// Reads the ignore files that live in `dir`. Like ripgrep, `.ignore`
+    /// beats `.gitignore`.
+    fn load_ignores(dir: &Path) -> Vec<Gitignore> {
+        [".gitignore", ".ignore"]
+            .iter()
+            .map(|name| Gitignore::from_file(&dir.join(name)))
+            .filter(|gitignore| !gitignore.is_empty())
+            .collect()
+    }
+
+    /// Reads the ignore files above `root` that still apply to it: those in
+    /// every directory up to the top of its git repository, and that
+    /// repository's `info/exclude`, which comes last. Outside a repository
+    /// there are none.
+    fn load_parents(root: &Path) -> Vec<Parent> {
+        let Ok(root) = fs::canonicalize(root) else {
+            return Vec::new();
+        };
+        let Some(repo) = root.ancestors().find(|dir| dir.join(".git").exists()) else {
+            return Vec::new();
+        };
+        let prefix = |dir: &Path| root.strip_prefix(dir).unwrap_or(&root).to_path_buf();
+
+        let mut parents: Vec<Parent> = root
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(repo))
+            .map(|dir| Parent {
+                prefix: prefix(dir),
+                ignores: Self::load_ignores(dir),
+            })
+            .collect();
+        parents.push(Parent {
+            prefix: prefix(repo),
+            ignores: vec![Gitignore::from_file(&repo.join(".git/info/exclude"))],
+        });
+        parents
+    }
+
+    /// Asks the ignore files from the deepest directory upwards. The first
+    /// one with an opinion about `path` decides.
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if is_dir && path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+
+        for level in self.stack.iter().rev() {
+            let relative = match path.strip_prefix(&level.dir) {
+                Ok(relative) => relative,
+                Err(_) => continue,
+            };
+            for gitignore in level.ignores.iter().rev() {
+                if let Some(verdict) = gitignore.matched(relative, is_dir) {
+                    return verdict == Verdict::Ignore;
+                }
+            }
+        }
+
+        let Some(relative) = self
+            .stack
+            .first()
+            .and_then(|root| path.strip_prefix(&root.dir).ok())
+        else {
+            return false;
+        };
+        for parent in &self.parents {
+            let relative = parent.prefix.join(relative);
+            for gitignore in parent.ignores.iter().rev() {
+                if let Some(verdict) = gitignore.matched(&relative, is_dir) {
+                    return verdict == Verdict::Ignore;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn is_hidden(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.'))
+    }
+}
+
+impl Iterator for Walk {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<io::Result<PathBuf>> {
+        // The root is always visited, even when it is hidden or a symlink.
+        if let Some(root) = self.root.take() {
+            if !root.is_dir() {
+                return Some(Ok(root));
+            }
+            if self.walker.ignore_files {
+                self.parents = Self::load_parents(&root);
+            }
+            if let Err(err) = self.push_dir(&root, 0) {
+                return Some(Err(with_path(err, &root)));
+            }
+        }
+
+        loop {
+            let level = self.stack.last_mut()?;
+            let depth = level.depth + 1;
+            let path = match level.entries.next() {
+                Some(path) => path,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+
+            if self.walker.max_depth.is_some_and(|max| depth > max) {
+                continue;
+            }
+            if !self.walker.hidden && Self::is_hidden(&path) {
+                continue;
+            }
+
+            let metadata = match fs::symlink_metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(err) => return Some(Err(with_path(err, &path))),
+            };
+            let metadata = if metadata.file_type().is_symlink() {
+                if !self.walker.follow_links {
+                    continue;
+                }
+                match fs::metadata(&path) {
+                    Ok(metadata) => metadata,
+                    Err(err) => return Some(Err(with_path(err, &path))),
+                }
+            } else {
+                metadata
+            };
+
+            if self.walker.ignore_files && self.is_ignored(&path, metadata.is_dir()) {
+                continue;
+            }
+
+            if metadata.is_dir() {
+                if let Err(err) = self.push_dir(&path, depth) {
+                    return Some(Err(with_path(err, &path)));
+                }
+            } else if metadata.is_file() {
+                return Some(Ok(path));
+            }
+        }
+    }
+}
+
+/// Makes sure an error message says which path it is about.
+pub(crate) fn with_path(err: io::Error, path: &Path) -> io::Error {
+    let prefix = path.display().to_string();
+    if err.to_string().starts_with(&prefix) {
+        err
+    } else {
+        io::Error::new(err.kind(), format!("{prefix}: {err}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    /// The files every test starts with.
+    const TREE: &[(&str, &str)] = &[
+        ("a.txt", "sun\n"),
+        ("sub/b.txt", "sun\n"),
+        ("sub/deeper/c.txt", "sun\n"),
+        (".hidden/d.txt", "sun\n"),
+        (".e.txt", "sun\n"),
+    ];
+
+    fn relative(walker: &Walker, root: &Path) -> Vec<String> {
+        walker
+            .walk(root)
+            .map(|path| {
+                let path = path.unwrap();
+                let path = path.strip_prefix(root).unwrap();
+                path.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn depth_and_hidden_files() {
+        let root = scratch_dir("depth", TREE);
+
+        let all = Walker::default();
+        assert_eq!(
+            vec!["a.txt", "sub/b.txt", "sub/deeper/c.txt"],
+            relative(&all, &root)
+        );
+
+        let shallow = Walker {
+            max_depth: Some(2),
+            hidden: true,
+            ..Walker::default()
+        };
+        assert_eq!(
+            vec![".e.txt", ".hidden/d.txt", "a.txt", "sub/b.txt"],
+            relative(&shallow, &root)
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn nested_ignore_files() {
+        let root = scratch_dir("ignore", TREE);
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::write(root.join(".git/info/exclude"), "a.txt\n").unwrap();
+        fs::write(root.join(".gitignore"), "deeper/\n").unwrap();
+        fs::write(root.join("sub/.ignore"), "!deeper/\n").unwrap();
+
+        assert_eq!(
+            vec!["sub/b.txt", "sub/deeper/c.txt"],
+            relative(&Walker::default(), &root)
+        );
+
+        let everything = Walker {
+            ignore_files: false,
+            ..Walker::default()
+        };
+        assert_eq!(
+            vec!["a.txt", "sub/b.txt", "sub/deeper/c.txt"],
+            relative(&everything, &root)
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn ignore_files_above_the_root() {
+        let root = scratch_dir("parents", TREE);
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::write(root.join(".git/info/exclude"), "b.txt\n").unwrap();
+        fs::write(root.join(".gitignore"), "deeper/\n").unwrap();
+        fs::create_dir_all(root.join("sub/other")).unwrap();
+        fs::write(root.join("sub/other/b.txt"), "sun\n").unwrap();
+        fs::write(root.join("sub/.ignore"), "!other/b.txt\n").unwrap();
+
+        // Only sub/other is walked, but the rules above it still apply.
+        assert_eq!(
+            vec!["b.txt"],
+            relative(&Walker::default(), &root.join("sub/other"))
+        );
+        assert_eq!(
+            vec!["other/b.txt"],
+            relative(&Walker::default(), &root.join("sub"))
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_are_reported() {
+        let root = scratch_dir("loop", TREE);
+        std::os::unix::fs::symlink(&root, root.join("sub/back")).unwrap();
+
+        let walker = Walker {
+            follow_links: true,
+            ..Walker::default()
+        };
+        let results: Vec<_> = walker.walk(&root).collect();
+        assert_eq!(3, results.iter().filter(|r| r.is_ok()).count());
+        let err = results.iter().find_map(|r| r.as_ref().err()).unwrap();
+        assert!(err.to_string().contains("loop"));
+
+        // Without following links the symlink is simply skipped.
+        assert_eq!(3, Walker::default().walk(&root).count());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}